@@ -1,23 +1,43 @@
-extern crate systray;
+extern crate tray_icon;
 extern crate winit;
 
-use std::{thread, env, sync};
+use std::{env, io, thread, sync, fs, time, path};
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::process::Command;
 use std::sync::{Mutex, Arc};
-use std::sync::mpsc::{Sender, TryRecvError};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+use tray_icon::menu::{CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu};
+use winit::event::{Event, StartCause};
+use winit::event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy};
+use winit::platform::run_return::EventLoopExtRunReturn;
+use crate::{calibration, control_server};
 use crate::kernel::Kernel;
 use crate::monitor_config::MonitorConfiguration;
-use crate::program_config::ProgramConfiguration;
-use crate::worker::{ControlMessage, Error, Worker};
+use crate::program_config::{self, ProgramConfiguration};
+use crate::worker::{ControlMessage, Error, OutputMode, Worker, WorkerState, WorkerStatus};
 
+const ICON_PATH: &str = "assets/icon.ico";
+// how often the worker is asked what it's doing, so the tray follows it without the menu being used
+const STATUS_POLL_INTERVAL: time::Duration = time::Duration::from_secs(1);
+// how long the poll waits for an answer before trying again later,
+// the worker doesn't answer while it's opening the capture or fading the strip out
+const STATUS_TIMEOUT: time::Duration = time::Duration::from_secs(2);
+const TRAY_BRIGHTNESS_LEVELS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
 
-pub fn spawn_worker_thread() -> Arc<Mutex<Sender<ControlMessage>>>{
-    let conv_kernel = Kernel::averaging(12, 12);
-    let p_config = ProgramConfiguration::load_from_file("assets/program_configuration.json");
+
+pub fn capture_kernel() -> Kernel {
+    Kernel::averaging(12, 12)
+}
+
+pub fn spawn_worker_thread(p_config: ProgramConfiguration) -> (Arc<Mutex<Sender<ControlMessage>>>, JoinHandle<()>){
+    let conv_kernel = capture_kernel();
     let m_config = MonitorConfiguration::load_from_file("assets/monitor_configuration.json");
     let (tx, rx) = sync::mpsc::channel();
-    thread::spawn(move || {
-        let mut test_worker = match Worker::new(p_config, m_config, conv_kernel, 0) {
+    let worker_thread = thread::spawn(move || {
+        let mut test_worker = match Worker::new(p_config, m_config, conv_kernel) {
             Ok(worker_inst) => worker_inst,
             Err(error) => {
                 eprintln!("Could not intialize worker");
@@ -29,52 +49,513 @@ pub fn spawn_worker_thread() -> Arc<Mutex<Sender<ControlMessage>>>{
             }
         };
         println!("Running");
-        loop {
-            match rx.try_recv() {
-                Ok(message) => {
-                    match message{
-                        ControlMessage::StopWorker => {println!("stopping");break},
-
-                        ControlMessage::UpdateConfiguration => {
-                            let p_config = ProgramConfiguration::load_from_file("assets/program_configuration.json");
-                            let m_config = MonitorConfiguration::load_from_file("assets/monitor_configuration.json");
-                            println!("{}\n{}", p_config, m_config);
-                            test_worker.update_settings(Some(p_config), None, None);
-                            println!("Updated configurations");
+        'worker: loop {
+            // handle every queued message before the next frame so fast senders can't build up a backlog
+            loop {
+                match rx.try_recv() {
+                    Ok(message) => {
+                        match message{
+                            ControlMessage::StopWorker(finished) => {
+                                println!("stopping");
+                                test_worker.shutdown();
+                                let _ = finished.send(());
+                                break 'worker
+                            },
+
+                            ControlMessage::UpdateConfiguration => {
+                                let p_config = ProgramConfiguration::load_from_file(program_config::CONFIGURATION_PATH);
+                                test_worker.update_settings(Some(p_config), None, None);
+                                println!("Updated configurations");
+                            }
+
+                            ControlMessage::ApplyConfiguration(p_config) => {
+                                test_worker.update_settings(Some(*p_config), None, None);
+                                println!("Applied configuration");
+                            }
+
+                            ControlMessage::UpdateMonitorConfiguration => {
+                                let m_config = MonitorConfiguration::load_from_file("assets/monitor_configuration.json");
+                                println!("{}", m_config);
+                                test_worker.update_settings(None, Some(m_config), None);
+                                println!("Updated monitor configuration");
+                            }
+
+                            ControlMessage::Pause => change_worker_state(&mut test_worker, WorkerState::Paused),
+
+                            ControlMessage::Resume => change_worker_state(&mut test_worker, WorkerState::Running),
+
+                            ControlMessage::Off => change_worker_state(&mut test_worker, WorkerState::Off),
+
+                            ControlMessage::SetBlankOnExit(blank_on_exit) => {
+                                test_worker.set_blank_on_exit(blank_on_exit);
+                                update_saved_configuration(|p_config| p_config.blank_on_exit = blank_on_exit);
+                            }
+
+                            ControlMessage::SetOutputMode(output_mode) => {
+                                test_worker.set_output_mode(output_mode);
+                                // an audio mode that can't open its input falls back to the previous mode
+                                let output_mode = test_worker.output_mode();
+                                println!("Output mode is now {}", output_mode);
+                                update_saved_configuration(|p_config| p_config.output_mode = output_mode);
+                            }
+
+                            ControlMessage::SetBrightness(brightness) => test_worker.set_brightness(brightness),
+
+                            ControlMessage::SetColourSettings(colour_settings) => test_worker.set_colour_settings(colour_settings),
+
+                            ControlMessage::SetInput{source, priority, content, timeout} => test_worker.set_input(source, priority, content, timeout),
+
+                            ControlMessage::ClearInput(source) => test_worker.clear_input(source),
+
+                            ControlMessage::SubscribeOutput(subscriber) => test_worker.add_output_subscriber(subscriber),
+
+                            ControlMessage::RequestStatus(reply) => {
+                                let _ = reply.send(test_worker.status());
+                            }
                         }
-                    }
-                },
-                Err(e) => {
-                    match e{
-                        TryRecvError::Empty => {}
-                        TryRecvError::Disconnected => {break}
-                    }
-                },
-            };
+                    },
+                    Err(e) => {
+                        match e{
+                            TryRecvError::Empty => {break}
+                            TryRecvError::Disconnected => {test_worker.shutdown(); break 'worker}
+                        }
+                    },
+                };
+            }
             test_worker.read_and_output();
             test_worker.tick();
         }
     });
-    Arc::new(Mutex::new(tx))
+    (Arc::new(Mutex::new(tx)), worker_thread)
+}
+
+pub fn stop_worker(worker_controller: &Arc<Mutex<Sender<ControlMessage>>>) {
+    // blocks until the worker has faded out, returns immediately if the worker has already stopped
+    let (finished_sender, finished_receiver) = sync::mpsc::channel();
+    if worker_controller.lock().unwrap().send(ControlMessage::StopWorker(finished_sender)).is_ok() {
+        // the sender is also dropped if the worker exits without acknowledging, so this can't hang
+        let _ = finished_receiver.recv();
+    }
+}
+
+fn change_worker_state(worker: &mut Worker, state: WorkerState) {
+    worker.set_state(state);
+    println!("Worker is now {}", state);
+    update_saved_configuration(|p_config| p_config.worker_state = state);
+}
+
+fn update_saved_configuration<F: FnOnce(&mut ProgramConfiguration)>(change: F) {
+    // changes made from the tray are persisted so the lights come back the same way after a restart
+    let path = program_config::CONFIGURATION_PATH;
+    let mut p_config = match ProgramConfiguration::try_load(path) {
+        Some(p_config) => p_config,
+        // nothing saved yet, so there's nothing to lose by starting from the defaults
+        None if !path::Path::new(path).exists() => ProgramConfiguration::default(),
+        None => {
+            // writing the defaults back would wipe everything else in a file that only has a typo in it
+            eprintln!("Unable to read {}, not saving the change", path);
+            return;
+        }
+    };
+    change(&mut p_config);
+    if let Err(error) = p_config.save_to_file(path) {
+        println!("Unable to save program configuration: {}", error);
+    }
+}
+
+
+#[derive(Clone)]
+enum TrayStatus {
+    // the worker hasn't answered yet
+    Starting,
+    Running(WorkerStatus),
+    Stopped
+}
+
+// what wakes up the tray's event loop
+enum TrayEvent {
+    Menu(MenuId),
+    Status(TrayStatus),
+    // /api/quit, handled the same as picking Quit
+    Quit,
+}
+
+#[derive(Clone)]
+enum TrayAction {
+    Configure,
+    ConfigureMonitors,
+    ToggleBlankOnExit,
+    Pause,
+    Resume,
+    Mode(OutputMode),
+    Off,
+    Brightness(f32),
+    SavedSettings,
+    Profile(String),
+    Quit,
+}
+
+fn request_status(worker_controller: &Arc<Mutex<Sender<ControlMessage>>>, timeout: time::Duration) -> Option<TrayStatus> {
+    // None when the worker is too busy to answer in time
+    let (status_sender, status_receiver) = sync::mpsc::channel();
+    if worker_controller.lock().unwrap().send(ControlMessage::RequestStatus(status_sender)).is_err() {
+        return Some(TrayStatus::Stopped);
+    }
+    match status_receiver.recv_timeout(timeout) {
+        Ok(status) => Some(TrayStatus::Running(status)),
+        Err(RecvTimeoutError::Timeout) => None,
+        Err(RecvTimeoutError::Disconnected) => Some(TrayStatus::Stopped)
+    }
+}
+
+fn spawn_status_poll(worker_controller: Arc<Mutex<Sender<ControlMessage>>>, event_loop_proxy: EventLoopProxy<TrayEvent>) -> Sender<()> {
+    /*
+    Sends the tray the worker's status every STATUS_POLL_INTERVAL, or straight away when something's sent on the
+    returned sender, so the tray never waits on the worker itself
+    */
+    let (poll_now_sender, poll_now_receiver) = sync::mpsc::channel();
+    thread::spawn(move || loop {
+        if let Some(status) = request_status(&worker_controller, STATUS_TIMEOUT) {
+            let stopped = matches!(status, TrayStatus::Stopped);
+            if event_loop_proxy.send_event(TrayEvent::Status(status)).is_err() || stopped {
+                break;
+            }
+        }
+        if let Err(RecvTimeoutError::Disconnected) = poll_now_receiver.recv_timeout(STATUS_POLL_INTERVAL) {
+            break;
+        }
+    });
+    poll_now_sender
+}
+
+fn load_icons() -> Option<(Icon, Icon)> {
+    // the second is a greyed out copy shown while something is wrong
+    let icon = image::open(ICON_PATH).ok()?;
+    let (width, height) = (icon.width(), icon.height());
+    let error_icon = icon.grayscale().to_rgba8();
+    Some((
+        Icon::from_rgba(icon.to_rgba8().into_raw(), width, height).ok()?,
+        Icon::from_rgba(error_icon.into_raw(), width, height).ok()?
+    ))
+}
+
+struct Tray {
+    worker_controller: Arc<Mutex<Sender<ControlMessage>>>,
+    // the windows the tray launches send their changes here
+    control_address: Option<SocketAddr>,
+    poll_now: Sender<()>,
+    actions: HashMap<MenuId, TrayAction>,
+    // kept alive for as long as the icon should stay in the tray
+    tray_icon: TrayIcon,
+    icons: Option<(Icon, Icon)>,
+    showing_error_icon: bool,
+    status_item: MenuItem,
+    blank_on_exit_item: CheckMenuItem,
+    mode_items: Vec<(OutputMode, CheckMenuItem)>,
+    off_item: CheckMenuItem,
+    brightness_items: Vec<(f32, CheckMenuItem)>,
+    status: TrayStatus,
+    // shown after the status until another profile is picked
+    profile_error: Option<String>,
 }
 
+impl Tray {
+    fn new(worker_controller: Arc<Mutex<Sender<ControlMessage>>>, control_address: Option<SocketAddr>, poll_now: Sender<()>) -> Tray {
+        let mut actions = HashMap::new();
+        let menu = Menu::new();
+        let mut add_item = |parent: &dyn Fn(&dyn IsMenuItem), text: &str, action: TrayAction| {
+            let item = MenuItem::new(text, true, None);
+            actions.insert(item.id().clone(), action);
+            parent(&item);
+        };
+        let append = |item: &dyn IsMenuItem| menu.append(item).expect("Unable to add item to tray menu");
+
+        // disabled so it reads as a label, it's kept up to date by the status poll
+        let status_item = MenuItem::new("Ambient lighting: starting", false, None);
+        append(&status_item);
+        append(&PredefinedMenuItem::separator());
+        add_item(&append, "Configure", TrayAction::Configure);
+        add_item(&append, "Configure Monitors", TrayAction::ConfigureMonitors);
+        let blank_on_exit_item = CheckMenuItem::new("Blank On Exit", true, false, None);
+        append(&blank_on_exit_item);
+        append(&PredefinedMenuItem::separator());
+        add_item(&append, "Pause", TrayAction::Pause);
+        add_item(&append, "Resume", TrayAction::Resume);
 
-pub fn setup_application(mut app: systray::Application, worker_controller: Arc<Mutex<Sender<ControlMessage>>>) -> systray::Application{
-    // The app won't do any events unless you tell it to wait for messages
-    // app.quit() quits the taskbar process and it will have to be reconstructed
-    app.set_icon_from_file("assets/icon.ico").expect("Unable to set icon for menu");
-    let worker_controller_copy = Arc::clone(&worker_controller);
-    app.add_menu_item("Configure", move |_application| {
-        Command::new(env::current_exe().unwrap().to_str().unwrap()).arg("configure_program").output().unwrap();
-        worker_controller_copy.lock().unwrap().send(ControlMessage::UpdateConfiguration).unwrap();
-        Ok::<_, systray::Error>(())
-    }).expect("Unable to add configure button to menu");
+        let mode_menu = Submenu::new("Mode", true);
+        let mut mode_items = Vec::new();
+        for output_mode in OutputMode::ALL {
+            let item = CheckMenuItem::new(output_mode.to_string(), true, false, None);
+            mode_menu.append(&item).expect("Unable to add mode to tray menu");
+            mode_items.push((output_mode, item));
+        }
+        let off_item = CheckMenuItem::new("Off", true, false, None);
+        mode_menu.append(&off_item).expect("Unable to add mode to tray menu");
+        append(&mode_menu);
 
-    app.add_menu_item("Quit", move |application| {
-        worker_controller.lock().unwrap().send(ControlMessage::StopWorker).unwrap();
-        application.quit();
-        Ok::<_, systray::Error>(())
-    }).expect("Unable to add quit button to menu");
+        let brightness_menu = Submenu::new("Brightness", true);
+        let mut brightness_items = Vec::new();
+        for brightness in TRAY_BRIGHTNESS_LEVELS {
+            let item = CheckMenuItem::new(format!("{:.0}%", brightness * 100.0), true, false, None);
+            brightness_menu.append(&item).expect("Unable to add brightness to tray menu");
+            brightness_items.push((brightness, item));
+        }
+        append(&brightness_menu);
+
+        // profiles are listed when the tray starts, ones added later show up after a restart
+        let profile_menu = Submenu::new("Profile", true);
+        let append_profile = |item: &dyn IsMenuItem| profile_menu.append(item).expect("Unable to add profile to tray menu");
+        add_item(&append_profile, "Saved Settings", TrayAction::SavedSettings);
+        for profile in program_config::list_profiles() {
+            add_item(&append_profile, profile.as_str(), TrayAction::Profile(profile.clone()));
+        }
+        append(&profile_menu);
 
-    app
-}
\ No newline at end of file
+        append(&PredefinedMenuItem::separator());
+        add_item(&append, "Quit", TrayAction::Quit);
+
+        actions.insert(blank_on_exit_item.id().clone(), TrayAction::ToggleBlankOnExit);
+        for (output_mode, item) in &mode_items {
+            actions.insert(item.id().clone(), TrayAction::Mode(*output_mode));
+        }
+        actions.insert(off_item.id().clone(), TrayAction::Off);
+        for (brightness, item) in &brightness_items {
+            actions.insert(item.id().clone(), TrayAction::Brightness(*brightness));
+        }
+
+        let icons = load_icons();
+        let mut builder = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip(status_item.text());
+        if let Some((icon, _error_icon)) = &icons {
+            builder = builder.with_icon(icon.clone());
+        }
+        Tray {
+            worker_controller,
+            control_address,
+            poll_now,
+            actions,
+            tray_icon: builder.build().expect("Unable to create tray icon"),
+            icons,
+            showing_error_icon: false,
+            status_item,
+            blank_on_exit_item,
+            mode_items,
+            off_item,
+            brightness_items,
+            status: TrayStatus::Starting,
+            profile_error: None,
+        }
+    }
+
+    fn status_text(&self) -> String {
+        let status_text = match &self.status {
+            TrayStatus::Starting => String::from("Ambient lighting: starting"),
+            TrayStatus::Running(status) => match &status.error {
+                Some(error) => format!("Ambient lighting: {}, {}, {}", status.state, status.output_mode, error),
+                None => format!(
+                    "Ambient lighting: {}, {}, {:.0} FPS, {:.0}% brightness, connected to {}",
+                    status.state, status.output_mode, status.frame_rate, status.brightness * 100.0, status.output
+                )
+            },
+            TrayStatus::Stopped => {
+                let p_config = ProgramConfiguration::load_from_file(program_config::CONFIGURATION_PATH);
+                let port_connected = serialport::available_ports()
+                    .map(|ports| ports.iter().any(|port| program_config::is_same_serial_port(&port.port_name, &p_config.serial_port)))
+                    .unwrap_or(false);
+                if !port_connected {
+                    format!("Ambient lighting: disconnected, serial port {} not found", p_config.serial_port)
+                } else {
+                    String::from("Ambient lighting: disconnected, the lights have stopped")
+                }
+            }
+        };
+        match &self.profile_error {
+            Some(profile_error) => format!("{}, {}", status_text, profile_error),
+            None => status_text
+        }
+    }
+
+    fn show_status(&mut self, status: TrayStatus) {
+        self.status = status;
+        let status_text = self.status_text();
+        self.status_item.set_text(status_text.as_str());
+        let _ = self.tray_icon.set_tooltip(Some(status_text));
+
+        let show_error_icon = matches!(self.status, TrayStatus::Running(WorkerStatus{error: Some(_), ..}) | TrayStatus::Stopped);
+        if show_error_icon != self.showing_error_icon {
+            if let Some((icon, error_icon)) = &self.icons {
+                let _ = self.tray_icon.set_icon(Some(if show_error_icon { error_icon.clone() } else { icon.clone() }));
+            }
+            self.showing_error_icon = show_error_icon;
+        }
+
+        // clicking a check item ticks it straight away, this puts it back to what the worker is really doing
+        let running = match &self.status {
+            TrayStatus::Running(status) => Some(status),
+            _ => None
+        };
+        self.blank_on_exit_item.set_checked(running.is_some_and(|status| status.blank_on_exit));
+        for (output_mode, item) in &self.mode_items {
+            item.set_checked(running.is_some_and(|status| status.state != WorkerState::Off && status.output_mode == *output_mode));
+        }
+        self.off_item.set_checked(running.is_some_and(|status| status.state == WorkerState::Off));
+        for (brightness, item) in &self.brightness_items {
+            item.set_checked(running.is_some_and(|status| (status.brightness - brightness).abs() < 0.005));
+        }
+    }
+
+    fn send(&self, messages: Vec<ControlMessage>) {
+        // a stopped worker is shown in the status rather than taking the tray down with it
+        {
+            let worker_controller = self.worker_controller.lock().unwrap();
+            for message in messages {
+                let _ = worker_controller.send(message);
+            }
+        }
+        // the worker answers after handling the messages so the status includes them
+        let _ = self.poll_now.send(());
+    }
+
+    fn handle_menu(&mut self, id: &MenuId) -> ControlFlow {
+        match self.actions.get(id) {
+            Some(action) => self.handle_action(action.clone()),
+            None => ControlFlow::Wait
+        }
+    }
+
+    fn handle_action(&mut self, action: TrayAction) -> ControlFlow {
+        match action {
+            TrayAction::Configure => launch_settings_configurer(Arc::clone(&self.worker_controller), self.control_address),
+            TrayAction::ConfigureMonitors => launch_monitor_configurer(Arc::clone(&self.worker_controller), self.control_address),
+            TrayAction::ToggleBlankOnExit => {
+                // flips what the worker has now, a profile or reload may have changed it since the tick was updated
+                let worker_controller = Arc::clone(&self.worker_controller);
+                let poll_now = self.poll_now.clone();
+                thread::spawn(move || {
+                    if let Some(TrayStatus::Running(status)) = request_status(&worker_controller, STATUS_TIMEOUT) {
+                        let enabled = !status.blank_on_exit;
+                        println!("Blank on exit: {}", enabled);
+                        let _ = worker_controller.lock().unwrap().send(ControlMessage::SetBlankOnExit(enabled));
+                    }
+                    let _ = poll_now.send(());
+                });
+            },
+            TrayAction::Pause => self.send(vec![ControlMessage::Pause]),
+            TrayAction::Resume => self.send(vec![ControlMessage::Resume]),
+            // picking a mode after turning the lights off turns them back on with it
+            TrayAction::Mode(output_mode) => self.send(vec![ControlMessage::SetOutputMode(output_mode), ControlMessage::Resume]),
+            TrayAction::Off => self.send(vec![ControlMessage::Off]),
+            // like a profile this lasts until the saved settings are reloaded, the saved brightness is set in Configure
+            TrayAction::Brightness(brightness) => self.send(vec![ControlMessage::SetBrightness(brightness)]),
+            TrayAction::SavedSettings => {
+                self.profile_error = None;
+                self.send(vec![ControlMessage::UpdateConfiguration]);
+            },
+            TrayAction::Profile(profile) => match ProgramConfiguration::load_profile(&profile) {
+                Some(p_config) => {
+                    self.profile_error = None;
+                    self.send(vec![ControlMessage::ApplyConfiguration(Box::new(p_config))]);
+                },
+                None => {
+                    eprintln!("Unable to load profile {}", profile);
+                    self.profile_error = Some(format!("unable to load profile {}", profile));
+                    self.show_status(self.status.clone());
+                }
+            },
+            TrayAction::Quit => {
+                stop_worker(&self.worker_controller);
+                return ControlFlow::Exit;
+            }
+        }
+        ControlFlow::Wait
+    }
+}
+
+pub fn run_tray(worker_controller: Arc<Mutex<Sender<ControlMessage>>>, control_address: Option<SocketAddr>, quit_receiver: Receiver<()>) {
+    // blocks until Quit is picked
+    let mut event_loop = EventLoopBuilder::<TrayEvent>::with_user_event().build();
+    let menu_event_proxy = Mutex::new(event_loop.create_proxy());
+    MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+        let _ = menu_event_proxy.lock().unwrap().send_event(TrayEvent::Menu(event.id));
+    }));
+    let poll_now = spawn_status_poll(Arc::clone(&worker_controller), event_loop.create_proxy());
+    let quit_proxy = event_loop.create_proxy();
+    thread::spawn(move || {
+        if quit_receiver.recv().is_ok() {
+            let _ = quit_proxy.send_event(TrayEvent::Quit);
+        }
+    });
+    let mut tray_parts = Some((worker_controller, poll_now));
+    let mut tray = None;
+    event_loop.run_return(move |event, _event_loop, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            // the icon can only be added once the event loop is running on some platforms
+            Event::NewEvents(StartCause::Init) => {
+                if let Some((worker_controller, poll_now)) = tray_parts.take() {
+                    tray = Some(Tray::new(worker_controller, control_address, poll_now));
+                }
+            },
+            Event::UserEvent(TrayEvent::Status(status)) => {
+                if let Some(tray) = &mut tray {
+                    tray.show_status(status);
+                }
+            },
+            Event::UserEvent(TrayEvent::Menu(id)) => {
+                if let Some(tray) = &mut tray {
+                    *control_flow = tray.handle_menu(&id);
+                }
+            },
+            Event::UserEvent(TrayEvent::Quit) => {
+                if let Some(tray) = &mut tray {
+                    *control_flow = tray.handle_action(TrayAction::Quit);
+                }
+            },
+            _ => {}
+        }
+    });
+}
+
+fn run_window_process(argument: &str, control_address: Option<SocketAddr>) -> io::Result<()> {
+    /*
+    Windows run in their own process since a process only gets one window event loop,
+    this blocks until the window is closed so it's called from its own thread to keep the tray working meanwhile.
+    The window sends what it changes to the control server like any other client
+    */
+    let mut command = Command::new(env::current_exe()?);
+    command.arg(argument);
+    if let Some(control_address) = control_address {
+        command.env(control_server::TRAY_ADDRESS_VARIABLE, control_address.to_string());
+    }
+    command.status()?;
+    Ok(())
+}
+
+fn launch_settings_configurer(worker_controller: Arc<Mutex<Sender<ControlMessage>>>, control_address: Option<SocketAddr>) {
+    thread::spawn(move || {
+        // the configurer reloads the settings when it saves and puts the saved colours back when it's closed without saving
+        if let Err(error) = run_window_process("--configure-program", control_address) {
+            eprintln!("Unable to launch settings configurer: {}", error);
+            return;
+        }
+        // closing the window part way through Test connection leaves nothing stuck on the strip
+        let _ = worker_controller.lock().unwrap().send(calibration::identify_message(None));
+    });
+}
+
+fn launch_monitor_configurer(worker_controller: Arc<Mutex<Sender<ControlMessage>>>, control_address: Option<SocketAddr>) {
+    thread::spawn(move || {
+        let saved_before = fs::read("assets/monitor_configuration.json").ok();
+        if let Err(error) = run_window_process("--configure-monitors", control_address) {
+            eprintln!("Unable to launch monitor setup wizard: {}", error);
+            return;
+        }
+        // closing the wizard while identifying leaves nothing stuck on the strip
+        let _ = worker_controller.lock().unwrap().send(calibration::identify_message(None));
+        // only reopen the capture when the wizard actually saved something
+        if fs::read("assets/monitor_configuration.json").ok() != saved_before {
+            let _ = worker_controller.lock().unwrap().send(ControlMessage::UpdateMonitorConfiguration);
+        }
+    });
+}
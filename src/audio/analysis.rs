@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use rustfft::{Fft, FftPlanner};
+use rustfft::num_complex::Complex;
+
+// samples analysed at a time, about 23ms at 44.1kHz
+pub const BLOCK_SIZE: usize = 1024;
+pub const BAND_COUNT: usize = 16;
+const LOWEST_FREQUENCY: f32 = 40.0;
+const HIGHEST_FREQUENCY: f32 = 16000.0;
+// bands below this are summed to find beats
+const BASS_CUTOFF: f32 = 150.0;
+// about a second of blocks to compare the current bass energy against
+const BEAT_HISTORY_LENGTH: usize = 43;
+const BEAT_THRESHOLD: f32 = 1.5;
+// per block decay of the running peaks used to normalise levels, lets quiet songs still fill the strip
+const PEAK_DECAY: f32 = 0.995;
+const MINIMUM_PEAK: f32 = 0.0001;
+
+#[derive(Clone, Debug, Default)]
+pub struct AudioFrame {
+    // loudness of each frequency band from bass to treble, from 0 to 1
+    pub bands: Vec<f32>,
+    // overall loudness from 0 to 1
+    pub level: f32,
+    pub beat: bool,
+}
+
+pub struct AudioAnalyser {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    // fft bin ranges covered by each band
+    band_bins: Vec<(usize, usize)>,
+    bass_band_count: usize,
+    band_peaks: Vec<f32>,
+    level_peak: f32,
+    bass_history: VecDeque<f32>,
+}
+
+impl AudioAnalyser {
+    pub fn new(sample_rate: u32) -> AudioAnalyser {
+        let bin_width = sample_rate as f32 / BLOCK_SIZE as f32;
+        let highest_frequency = HIGHEST_FREQUENCY.min(sample_rate as f32 / 2.0);
+
+        // bands are spaced logarithmically since that's closer to how pitch is heard
+        let mut band_bins = Vec::with_capacity(BAND_COUNT);
+        let mut bass_band_count = 0;
+        for band in 0..BAND_COUNT {
+            let start_frequency = LOWEST_FREQUENCY * (highest_frequency / LOWEST_FREQUENCY).powf(band as f32 / BAND_COUNT as f32);
+            let end_frequency = LOWEST_FREQUENCY * (highest_frequency / LOWEST_FREQUENCY).powf((band + 1) as f32 / BAND_COUNT as f32);
+            let start_bin = ((start_frequency / bin_width) as usize).clamp(1, BLOCK_SIZE / 2 - 1);
+            let end_bin = ((end_frequency / bin_width) as usize).clamp(start_bin + 1, BLOCK_SIZE / 2);
+            band_bins.push((start_bin, end_bin));
+            if end_frequency <= BASS_CUTOFF {
+                bass_band_count = band + 1;
+            }
+        }
+
+        AudioAnalyser {
+            fft: FftPlanner::new().plan_fft_forward(BLOCK_SIZE),
+            // hann window to reduce leakage between bins
+            window: (0..BLOCK_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (BLOCK_SIZE - 1) as f32).cos()).collect(),
+            band_bins,
+            bass_band_count: bass_band_count.max(1),
+            band_peaks: vec![MINIMUM_PEAK; BAND_COUNT],
+            level_peak: MINIMUM_PEAK,
+            bass_history: VecDeque::with_capacity(BEAT_HISTORY_LENGTH),
+        }
+    }
+
+    pub fn analyse(&mut self, samples: &[f32]) -> AudioFrame {
+        /*
+        samples should be BLOCK_SIZE mono samples between -1 and 1
+        */
+        let mut buffer: Vec<Complex<f32>> = samples.iter().zip(self.window.iter())
+            .map(|(sample, weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        buffer.resize(BLOCK_SIZE, Complex::new(0.0, 0.0));
+        self.fft.process(&mut buffer);
+
+        let raw_bands: Vec<f32> = self.band_bins.iter()
+            .map(|(start_bin, end_bin)| buffer[*start_bin..*end_bin].iter().map(|bin| bin.norm()).sum::<f32>() / (end_bin - start_bin) as f32)
+            .collect();
+
+        let mut bands = Vec::with_capacity(BAND_COUNT);
+        for (band_value, peak) in raw_bands.iter().zip(self.band_peaks.iter_mut()) {
+            *peak = (*peak * PEAK_DECAY).max(*band_value).max(MINIMUM_PEAK);
+            bands.push(band_value / *peak);
+        }
+
+        let level = (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len().max(1) as f32).sqrt();
+        self.level_peak = (self.level_peak * PEAK_DECAY).max(level).max(MINIMUM_PEAK);
+
+        // a beat is a burst of bass energy well above the recent average
+        let bass_energy: f32 = raw_bands[..self.bass_band_count].iter().sum();
+        let average_bass = if self.bass_history.is_empty() {
+            bass_energy
+        } else {
+            self.bass_history.iter().sum::<f32>() / self.bass_history.len() as f32
+        };
+        let beat = self.bass_history.len() == BEAT_HISTORY_LENGTH && bass_energy > average_bass * BEAT_THRESHOLD;
+        if self.bass_history.len() == BEAT_HISTORY_LENGTH {
+            self.bass_history.pop_front();
+        }
+        self.bass_history.push_back(bass_energy);
+
+        AudioFrame {
+            bands,
+            level: level / self.level_peak,
+            beat
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//Audio visualiser that turns PCM input into LED colours
+pub mod analysis;
+pub mod source;
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::thread;
+use std::time::Instant;
+use crate::effects::{self, LedPlacement};
+use self::analysis::{AudioAnalyser, AudioFrame, BAND_COUNT, BLOCK_SIZE};
+
+// seconds for a beat pulse to fade back to black
+const PULSE_DECAY_TIME: f32 = 0.3;
+// analysed blocks buffered for the worker, older blocks are dropped while it isn't reading
+const FRAME_BUFFER_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioStyle {
+    VuMeter,
+    Spectrum,
+    BeatPulse,
+}
+
+impl AudioStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioStyle::VuMeter => "audio_vu_meter",
+            AudioStyle::Spectrum => "audio_spectrum",
+            AudioStyle::BeatPulse => "audio_beat_pulse"
+        }
+    }
+}
+
+impl Display for AudioStyle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                AudioStyle::VuMeter => "Audio VU Meter",
+                AudioStyle::Spectrum => "Audio Spectrum",
+                AudioStyle::BeatPulse => "Audio Beat Pulse"
+            }
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioInputSettings {
+    // "pulse" for the default PulseAudio/PipeWire monitor, "stdin" for raw PCM or the path of a WAV file
+    pub input: String,
+    // format of raw PCM on stdin, WAV files and PulseAudio describe their own
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl Default for AudioInputSettings {
+    fn default() -> Self {
+        AudioInputSettings {
+            input: String::from("pulse"),
+            sample_rate: 44100,
+            channels: 2
+        }
+    }
+}
+
+pub struct AudioVisualiser {
+    receiver: Receiver<AudioFrame>,
+    latest: AudioFrame,
+    pulse: f32,
+    last_render: Instant,
+}
+
+impl AudioVisualiser {
+    pub fn start(settings: &AudioInputSettings) -> io::Result<AudioVisualiser> {
+        /*
+        Opens the audio input and analyses it on its own thread, the thread stops once the visualiser is dropped
+        */
+        let mut audio_source = source::open_audio_source(settings)?;
+        let (sender, receiver) = mpsc::sync_channel(FRAME_BUFFER_SIZE);
+
+        thread::spawn(move || {
+            let mut analyser = AudioAnalyser::new(audio_source.sample_rate());
+            let mut samples = vec![0.0; BLOCK_SIZE];
+            loop {
+                if let Err(error) = audio_source.read(&mut samples) {
+                    eprintln!("Audio input stopped: {}", error);
+                    break
+                }
+                match sender.try_send(analyser.analyse(&samples)) {
+                    Ok(_) | Err(TrySendError::Full(_)) => {},
+                    Err(TrySendError::Disconnected(_)) => break
+                }
+            }
+        });
+
+        Ok(AudioVisualiser {
+            receiver,
+            latest: AudioFrame::default(),
+            pulse: 0.0,
+            last_render: Instant::now()
+        })
+    }
+
+    fn receive_frames(&mut self) -> bool {
+        // keeps the newest analysis and reports whether a beat happened in any block since the last render
+        let mut beat = false;
+        // stops once nothing is waiting, or the analysis thread has stopped and everything it sent has been read
+        while let Ok(frame) = self.receiver.try_recv() {
+            beat |= frame.beat;
+            self.latest = frame;
+        }
+        beat
+    }
+
+    pub fn render(&mut self, style: AudioStyle, colour: [u8; 3], leds: &[LedPlacement]) -> Vec<u8> {
+        let beat = self.receive_frames();
+        let elapsed = self.last_render.elapsed().as_secs_f32();
+        self.last_render = Instant::now();
+        self.pulse = if beat { 1.0 } else { (self.pulse - elapsed / PULSE_DECAY_TIME).max(0.0) };
+
+        let mut output = Vec::with_capacity(leds.len() * 3);
+        for led in leds {
+            let led_colour = match style {
+                AudioStyle::VuMeter => {
+                    // each side fills up from where its strip starts, green at the bottom to red at the top
+                    if led.side_progress <= self.latest.level {
+                        effects::hsv_to_rgb((1.0 - led.side_progress) / 3.0, 1.0, 1.0)
+                    } else {
+                        [0, 0, 0]
+                    }
+                },
+                AudioStyle::Spectrum => {
+                    // bass at the start of each side through to treble at the end
+                    let band = ((led.side_progress * BAND_COUNT as f32) as usize).min(BAND_COUNT - 1);
+                    let band_level = self.latest.bands.get(band).copied().unwrap_or(0.0);
+                    effects::hsv_to_rgb(band as f32 / BAND_COUNT as f32 * 0.75, 1.0, band_level.clamp(0.0, 1.0))
+                },
+                AudioStyle::BeatPulse => {
+                    [(colour[0] as f32 * self.pulse) as u8, (colour[1] as f32 * self.pulse) as u8, (colour[2] as f32 * self.pulse) as u8]
+                }
+            };
+            output.extend_from_slice(&led_colour);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::path::{Path, PathBuf};
+    use std::{env, fs, process, thread};
+    use std::time::Duration;
+    use super::*;
+    use super::source::{AudioSource, WavSource};
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn write_wav(name: &str, samples: &[f32]) -> PathBuf {
+        let path = env::temp_dir().join(format!("ambient_lighting_{}_{}.wav", name, process::id()));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in samples {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    fn tone(frequency: f32, amplitude: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    fn noise(amplitude: f32, seconds: f32) -> Vec<f32> {
+        // a fixed generator so the test always hears the same noise
+        let mut state: u32 = 12345;
+        (0..(seconds * SAMPLE_RATE as f32) as usize).map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            amplitude * ((state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0)
+        }).collect()
+    }
+
+    fn analyse_wav(path: &Path, sample_count: usize) -> Vec<AudioFrame> {
+        // only whole blocks, the file loops so a partial one would start again from the beginning
+        let mut wav = WavSource::open(path).unwrap();
+        let mut analyser = AudioAnalyser::new(wav.sample_rate());
+        let mut samples = vec![0.0; BLOCK_SIZE];
+        (0..sample_count / BLOCK_SIZE).map(|_| {
+            wav.read(&mut samples).unwrap();
+            analyser.analyse(&samples)
+        }).collect()
+    }
+
+    fn strip(led_count: usize) -> Vec<LedPlacement> {
+        (0..led_count).map(|led| LedPlacement {
+            position: [led as f32 / led_count as f32, 0.0],
+            side_progress: led as f32 / (led_count - 1) as f32,
+        }).collect()
+    }
+
+    fn lit_leds(colours: &[u8]) -> usize {
+        colours.chunks(3).filter(|colour| colour.iter().any(|channel| *channel > 0)).count()
+    }
+
+    fn render_until<F: Fn(&AudioVisualiser, usize) -> bool>(visualiser: &mut AudioVisualiser, leds: &[LedPlacement], done: F) -> usize {
+        // polled against a deadline instead of sleeping a fixed time, so a busy machine only makes the test slower
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let lit = lit_leds(&visualiser.render(AudioStyle::VuMeter, [255, 255, 255], leds));
+            if done(visualiser, lit) || Instant::now() > deadline {
+                return lit
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn tone_lands_in_its_band() {
+        // noise first so every band has heard something before the tone plays
+        let frequency = 1000.0;
+        let mut samples = noise(0.5, 0.5);
+        samples.extend(tone(frequency, 0.5, 0.2));
+        let path = write_wav("tone", &samples);
+        let frames = analyse_wav(&path, samples.len());
+        fs::remove_file(&path).unwrap();
+
+        let bands = &frames.last().unwrap().bands;
+        let loudest = (0..BAND_COUNT).max_by(|a, b| bands[*a].total_cmp(&bands[*b])).unwrap();
+        // the bands are spaced logarithmically from 40Hz to 16kHz
+        let expected = ((frequency / 40.0).ln() / (16000.0_f32 / 40.0).ln() * BAND_COUNT as f32) as usize;
+        assert_eq!(loudest, expected, "bands: {:?}", bands);
+        for (band, level) in bands.iter().enumerate() {
+            if band.abs_diff(expected) > 2 {
+                assert!(*level < 0.5, "band {} is at {} for a {}Hz tone", band, level, frequency);
+            }
+        }
+    }
+
+    #[test]
+    fn beat_follows_a_burst_of_bass() {
+        // over a second of silence so there's a full history to compare against, then a kick
+        let quiet_seconds = 1.5;
+        let mut samples = tone(60.0, 0.0, quiet_seconds);
+        samples.extend(tone(60.0, 0.9, 0.1));
+        let path = write_wav("beat", &samples);
+        let frames = analyse_wav(&path, samples.len());
+        fs::remove_file(&path).unwrap();
+
+        let first_loud_block = (quiet_seconds * SAMPLE_RATE as f32) as usize / BLOCK_SIZE + 1;
+        assert!(!frames[..first_loud_block - 1].iter().any(|frame| frame.beat), "beat heard in the silence");
+        assert!(frames[first_loud_block - 1..].iter().any(|frame| frame.beat), "no beat heard in the bass");
+    }
+
+    #[test]
+    fn vu_meter_follows_the_level() {
+        let leds = strip(20);
+        let mut lit = Vec::new();
+        for (name, amplitude) in [("silence", 0.0), ("loud", 0.8)] {
+            let path = write_wav(name, &tone(440.0, amplitude, 0.5));
+            let mut visualiser = AudioVisualiser::start(&AudioInputSettings {
+                input: path.to_str().unwrap().to_string(),
+                ..AudioInputSettings::default()
+            }).unwrap();
+            // silence has to have been analysed for the count to mean anything, a loud tone fills the meter once it has been
+            lit.push(render_until(&mut visualiser, &leds, |visualiser, lit| {
+                !visualiser.latest.bands.is_empty() && (amplitude == 0.0 || lit > leds.len() / 2)
+            }));
+            fs::remove_file(&path).unwrap();
+        }
+        // silence only lights the first LED, which sits at the very start of the meter
+        assert!(lit[0] <= 1, "{} LEDs lit in silence", lit[0]);
+        assert!(lit[1] > leds.len() / 2, "only {} LEDs lit for a loud tone", lit[1]);
+    }
+}
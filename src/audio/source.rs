@@ -0,0 +1,172 @@
+use std::{io, thread};
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use crate::audio::AudioInputSettings;
+
+pub trait AudioSource: Send {
+    fn sample_rate(&self) -> u32;
+
+    // fills samples with mono audio between -1 and 1, blocking until enough is available
+    fn read(&mut self, samples: &mut [f32]) -> io::Result<()>;
+}
+
+pub fn open_audio_source(settings: &AudioInputSettings) -> io::Result<Box<dyn AudioSource>> {
+    match settings.input.as_str() {
+        "stdin" => Ok(Box::new(RawPcmSource::new(Box::new(io::stdin()), settings.sample_rate, settings.channels))),
+        "pulse" => open_pulse_source(),
+        path => Ok(Box::new(WavSource::open(Path::new(path))?))
+    }
+}
+
+pub struct WavSource {
+    // decoded and mixed down to mono up front
+    samples: Vec<f32>,
+    sample_rate: u32,
+    position: usize,
+    started: Instant,
+    samples_played: u64,
+}
+
+impl WavSource {
+    pub fn open(path: &Path) -> io::Result<WavSource> {
+        let reader = hound::WavReader::open(path).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let spec = reader.spec();
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.into_samples::<f32>().filter_map(Result::ok).collect(),
+            hound::SampleFormat::Int => {
+                let full_scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader.into_samples::<i32>().filter_map(Result::ok).map(|sample| sample as f32 / full_scale).collect()
+            }
+        };
+        let channels = spec.channels.max(1) as usize;
+        let samples: Vec<f32> = interleaved.chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        if samples.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has no audio", path.display())));
+        }
+
+        Ok(WavSource {
+            samples,
+            sample_rate: spec.sample_rate,
+            position: 0,
+            started: Instant::now(),
+            samples_played: 0
+        })
+    }
+}
+
+impl AudioSource for WavSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, samples: &mut [f32]) -> io::Result<()> {
+        // played back in real time and looped so the LEDs react as if the file were playing
+        for sample in samples.iter_mut() {
+            *sample = self.samples[self.position];
+            self.position = (self.position + 1) % self.samples.len();
+        }
+        self.samples_played += samples.len() as u64;
+        let play_time = self.started + Duration::from_secs_f64(self.samples_played as f64 / self.sample_rate as f64);
+        if let Some(wait_time) = play_time.checked_duration_since(Instant::now()) {
+            thread::sleep(wait_time);
+        }
+        Ok(())
+    }
+}
+
+pub struct RawPcmSource {
+    // interleaved signed 16 bit little endian samples
+    reader: Box<dyn Read + Send>,
+    sample_rate: u32,
+    channels: usize,
+    buffer: Vec<u8>,
+}
+
+impl RawPcmSource {
+    pub fn new(reader: Box<dyn Read + Send>, sample_rate: u32, channels: u16) -> RawPcmSource {
+        RawPcmSource {
+            reader,
+            sample_rate,
+            channels: channels.max(1) as usize,
+            buffer: Vec::new()
+        }
+    }
+}
+
+impl AudioSource for RawPcmSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, samples: &mut [f32]) -> io::Result<()> {
+        self.buffer.resize(samples.len() * self.channels * 2, 0);
+        self.reader.read_exact(&mut self.buffer)?;
+        for (sample, frame) in samples.iter_mut().zip(self.buffer.chunks(self.channels * 2)) {
+            let total: f32 = frame.chunks(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0).sum();
+            *sample = total / self.channels as f32;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "pulseaudio")]
+pub struct PulseSource {
+    connection: libpulse_simple_binding::Simple,
+    buffer: Vec<u8>,
+}
+
+#[cfg(feature = "pulseaudio")]
+const PULSE_SAMPLE_RATE: u32 = 44100;
+
+#[cfg(feature = "pulseaudio")]
+fn open_pulse_source() -> io::Result<Box<dyn AudioSource>> {
+    use libpulse_binding::sample::{Format, Spec};
+    use libpulse_binding::stream::Direction;
+
+    let spec = Spec {
+        format: Format::F32le,
+        channels: 1,
+        rate: PULSE_SAMPLE_RATE
+    };
+    // records whatever is playing on the default output, this also works on PipeWire through pipewire-pulse
+    let connection = libpulse_simple_binding::Simple::new(
+        None,
+        "Monitor Ambient Lighting",
+        Direction::Record,
+        Some("@DEFAULT_MONITOR@"),
+        "Audio visualiser",
+        &spec,
+        None,
+        None
+    ).map_err(|error| io::Error::new(io::ErrorKind::Other, format!("Unable to connect to PulseAudio: {}", error)))?;
+
+    Ok(Box::new(PulseSource {
+        connection,
+        buffer: Vec::new()
+    }))
+}
+
+#[cfg(not(feature = "pulseaudio"))]
+fn open_pulse_source() -> io::Result<Box<dyn AudioSource>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Built without PulseAudio support, enable the pulseaudio feature or use a WAV file or stdin"))
+}
+
+#[cfg(feature = "pulseaudio")]
+impl AudioSource for PulseSource {
+    fn sample_rate(&self) -> u32 {
+        PULSE_SAMPLE_RATE
+    }
+
+    fn read(&mut self, samples: &mut [f32]) -> io::Result<()> {
+        self.buffer.resize(samples.len() * 4, 0);
+        self.connection.read(&mut self.buffer)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, format!("PulseAudio read failed: {}", error)))?;
+        for (sample, bytes) in samples.iter_mut().zip(self.buffer.chunks(4)) {
+            *sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        Ok(())
+    }
+}
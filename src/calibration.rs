@@ -0,0 +1,143 @@
+//Lights the LEDs one at a time or a side at a time to check the monitor configuration matches the wiring
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use json::object;
+use crate::control_server;
+use crate::monitor_config::MonitorConfiguration;
+use crate::multiplexer::{InputContent, InputSource, CALIBRATION_PRIORITY};
+use crate::side::Side;
+use crate::worker::ControlMessage;
+
+const IDENTIFY_COLOUR: [u8; 3] = [255, 255, 255];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentifyPattern {
+    // only this LED, counting from 0 in chain order
+    Led(usize),
+    // every side in its own colour
+    Sides,
+}
+
+pub fn side_colour(side: Side) -> [u8; 3] {
+    // the same colours as the side_bars test pattern
+    match side {
+        Side::TOP => [255, 0, 0],
+        Side::RIGHT => [0, 255, 0],
+        Side::BOTTOM => [0, 0, 255],
+        Side::LEFT => [255, 255, 0],
+        Side::ERROR => [0, 0, 0],
+    }
+}
+
+pub fn identify_colours(monitor_config: &MonitorConfiguration, pattern: IdentifyPattern) -> Vec<u8> {
+    monitor_config.get_led_sides().iter().enumerate()
+        .flat_map(|(led, (side, _progress))| match pattern {
+            IdentifyPattern::Led(lit_led) => if led == lit_led { IDENTIFY_COLOUR } else { [0, 0, 0] },
+            IdentifyPattern::Sides => side_colour(*side),
+        })
+        .collect()
+}
+
+pub fn describe_led(monitor_config: &MonitorConfiguration, led: usize) -> String {
+    // where the LED should be on the physical strip according to the configuration
+    let total = monitor_config.get_led_sides().len();
+    let mut first_on_strip = 0;
+    for monitor in &monitor_config.monitors {
+        for strip in &monitor.led_order.data {
+            let count = monitor.led_distribution.get(strip.side);
+            if led < first_on_strip + count {
+                return format!(
+                    "LED {} of {}: monitor {}, {} side, {} of {} counting from the {}",
+                    led + 1, total, monitor.monitor_number, strip.side.to_string().to_lowercase(),
+                    led - first_on_strip + 1, count, strip.direction.opposite().to_string().to_lowercase()
+                );
+            }
+            first_on_strip += count;
+        }
+    }
+    format!("LED {} is past the end of the {} configured LEDs", led + 1, total)
+}
+
+pub fn identify_message(colours: Option<Vec<u8>>) -> ControlMessage {
+    // None stops identifying and gives the LEDs back to whatever was showing before
+    match colours {
+        Some(colours) => ControlMessage::SetInput {
+            source: InputSource::Calibration,
+            priority: CALIBRATION_PRIORITY,
+            content: InputContent::Colours(colours),
+            timeout: None,
+        },
+        None => ControlMessage::ClearInput(InputSource::Calibration),
+    }
+}
+
+pub fn send_identify_to_tray(colours: Option<&[u8]>) -> bool {
+    // the windows launched from the tray light the LEDs through its control server, None stops identifying
+    let body = match colours {
+        Some(colours) => object!{"colours": colours.to_vec()},
+        None => object!{}
+    };
+    control_server::post_to_tray("/api/identify", body)
+}
+
+pub fn run_identify_prompt(worker_controller: &Arc<Mutex<Sender<ControlMessage>>>, monitor_config: &MonitorConfiguration) {
+    /*
+    Steps through the LEDs from the terminal until q or the end of stdin,
+    the LEDs go back to what they were showing afterwards
+    */
+    let total = monitor_config.get_led_sides().len();
+    if total == 0 {
+        eprintln!("No LEDs are configured in the monitor configuration");
+        return;
+    }
+    println!("Enter lights the next LED, p the previous one, a number jumps to that LED, s lights each side in its own colour and q stops");
+    let stdin = io::stdin();
+    let mut current_led = 0;
+    let mut pattern = IdentifyPattern::Led(current_led);
+    loop {
+        if worker_controller.lock().unwrap().send(identify_message(Some(identify_colours(monitor_config, pattern)))).is_err() {
+            eprintln!("The worker has stopped");
+            return;
+        }
+        match pattern {
+            IdentifyPattern::Led(led) => println!("{}", describe_led(monitor_config, led)),
+            IdentifyPattern::Sides => println!("Top red, right green, bottom blue, left yellow"),
+        }
+        print!("> ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        pattern = match line.trim() {
+            "" | "n" => {
+                // coming back from the sides carries on from the LED shown before them
+                if pattern != IdentifyPattern::Sides {
+                    current_led = (current_led + 1) % total;
+                }
+                IdentifyPattern::Led(current_led)
+            }
+            "p" => {
+                if pattern != IdentifyPattern::Sides {
+                    current_led = (current_led + total - 1) % total;
+                }
+                IdentifyPattern::Led(current_led)
+            }
+            "s" => IdentifyPattern::Sides,
+            "q" => break,
+            number => match number.parse::<usize>() {
+                Ok(led) if (1..=total).contains(&led) => {
+                    current_led = led - 1;
+                    IdentifyPattern::Led(current_led)
+                }
+                _ => {
+                    println!("There are LEDs 1 to {}", total);
+                    pattern
+                }
+            }
+        };
+    }
+    let _ = worker_controller.lock().unwrap().send(identify_message(None));
+}
@@ -0,0 +1,269 @@
+//Samples LED colours from the frames of every configured monitor
+use std::io;
+use std::time::{Duration, Instant};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::frame_source::{self, FrameSource, FrameSourceSettings};
+use crate::kernel::Kernel;
+use crate::monitor_config::MonitorConfiguration;
+
+// longest the benchmark waits for each downscale factor to get its frames
+const BENCHMARK_TIME_LIMIT: Duration = Duration::from_secs(30);
+
+struct CaptureSlot {
+    frame_source: Box<dyn FrameSource>,
+    // sample points of the LEDs this source covers at full resolution, in chain order
+    pixel_locations: Vec<[usize; 2]>,
+    // the same points in the downscaled image
+    sample_locations: Vec<[usize; 2]>,
+    // colours sampled from the last frame, reused until the source has a new one
+    colours: Vec<u8>,
+    // reused between frames, only filled when downscaling
+    downscaled_image: Vec<u8>,
+}
+
+pub struct MonitorCapture {
+    // in chain order, so sampling them one after another gives the colours in the order the strip expects
+    slots: Vec<CaptureSlot>,
+    // frames are shrunk by this much in each direction before sampling, 1 samples at full resolution
+    downscale: usize,
+    // LEDs are split across this many threads while sampling, 1 samples on the worker thread
+    sampling_threads: usize,
+    // kept for as long as the thread count stays the same so no threads are started while sampling,
+    // None samples on the worker thread
+    sampling_pool: Option<ThreadPool>,
+    kernel: Kernel,
+    // the kernel shrunk with the image so it still covers the same area of the screen, rebuilt when either changes
+    downscaled_kernel: Kernel,
+}
+
+impl MonitorCapture {
+    pub fn open(settings: &FrameSourceSettings, monitor_config: &MonitorConfiguration, kernel: &Kernel, downscale: usize, sampling_threads: usize) -> io::Result<MonitorCapture> {
+        let mut slots = Vec::new();
+        if settings.input == "screen" {
+            for monitor in &monitor_config.monitors {
+                let frame_source = frame_source::open_frame_source(settings, monitor, kernel)?;
+                let pixel_locations = monitor.get_pixel_locations(kernel, frame_source.width(), frame_source.height());
+                slots.push(CaptureSlot::new(frame_source, pixel_locations));
+            }
+        } else if let Some(first_monitor) = monitor_config.monitors.first() {
+            // stand in sources like stdin can only be opened once, so every monitor samples from the same frames
+            let frame_source = frame_source::open_frame_source(settings, first_monitor, kernel)?;
+            let screen_sizes = vec![(frame_source.width(), frame_source.height()); monitor_config.monitors.len()];
+            let pixel_locations = monitor_config.get_pixel_locations(kernel, &screen_sizes);
+            slots.push(CaptureSlot::new(frame_source, pixel_locations));
+        }
+        let mut capture = MonitorCapture {
+            slots,
+            downscale: 1,
+            sampling_threads: 1,
+            sampling_pool: None,
+            kernel: *kernel,
+            downscaled_kernel: *kernel
+        };
+        capture.set_downscale(downscale);
+        capture.set_sampling_threads(sampling_threads);
+        Ok(capture)
+    }
+
+    pub fn set_downscale(&mut self, downscale: usize) {
+        self.downscale = downscale.max(1);
+        self.downscaled_kernel = self.kernel.downscaled(self.downscale);
+        for slot in &mut self.slots {
+            let (width, height) = (slot.frame_source.width() / self.downscale, slot.frame_source.height() / self.downscale);
+            slot.sample_locations = slot.pixel_locations.iter()
+                .map(|location| [(location[0] / self.downscale).min(width.saturating_sub(1)), (location[1] / self.downscale).min(height.saturating_sub(1))])
+                .collect();
+            // colours from the old sample locations are dropped until the next frame
+            slot.colours.clear();
+        }
+    }
+
+    pub fn downscale(&self) -> usize {
+        self.downscale
+    }
+
+    pub fn set_kernel(&mut self, kernel: &Kernel) {
+        self.kernel = *kernel;
+        self.downscaled_kernel = kernel.downscaled(self.downscale);
+    }
+
+    pub fn set_sampling_threads(&mut self, sampling_threads: usize) {
+        let sampling_threads = sampling_threads.max(1);
+        if sampling_threads == self.sampling_threads {
+            return;
+        }
+        self.sampling_threads = sampling_threads;
+        self.sampling_pool = if sampling_threads == 1 {
+            None
+        } else {
+            match ThreadPoolBuilder::new().num_threads(sampling_threads).thread_name(|index| format!("sampling {}", index)).build() {
+                Ok(pool) => Some(pool),
+                Err(error) => {
+                    eprintln!("Unable to start {} sampling threads, sampling on one: {}", sampling_threads, error);
+                    None
+                }
+            }
+        };
+    }
+
+    pub fn sampling_threads(&self) -> usize {
+        self.sampling_threads
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.slots.iter().map(|slot| slot.pixel_locations.len()).sum()
+    }
+
+    pub fn layout(&self) -> (usize, usize, Vec<[usize; 2]>) {
+        /*
+        Monitors are placed side by side from left to right in configuration order,
+        returns the size of the combined area and every sample point within it
+        */
+        let (mut width, mut height) = (0, 0);
+        let mut pixel_locations = Vec::with_capacity(self.led_count());
+        for slot in &self.slots {
+            pixel_locations.extend(slot.pixel_locations.iter().map(|location| [location[0] + width, location[1]]));
+            width += slot.frame_source.width();
+            height = height.max(slot.frame_source.height());
+        }
+        (width, height, pixel_locations)
+    }
+
+    pub fn sample(&mut self, output_colours: &mut Vec<u8>) -> bool {
+        /*
+        Samples straight from each source's frame buffer without copying it and writes every LED's colour
+        into output_colours. Returns false and leaves output_colours alone while no monitor has a new frame,
+        monitors that haven't captured anything yet are black
+        */
+        let mut new_frame = false;
+        for slot in &mut self.slots {
+            let (width, height) = (slot.frame_source.width(), slot.frame_source.height());
+            // frames that fail for any reason other than not being ready yet are skipped the same way
+            if let Ok(frame) = slot.frame_source.frame() {
+                if self.downscale == 1 {
+                    sample_points(&self.kernel, &frame, width, height, &slot.sample_locations, self.sampling_pool.as_ref(), &mut slot.colours);
+                } else {
+                    let (width, height) = downscale_image(&frame, width, height, self.downscale, &mut slot.downscaled_image);
+                    sample_points(&self.downscaled_kernel, &slot.downscaled_image, width, height, &slot.sample_locations, self.sampling_pool.as_ref(), &mut slot.colours);
+                }
+                new_frame = true;
+            }
+        }
+        if !new_frame {
+            return false;
+        }
+
+        output_colours.clear();
+        for slot in &self.slots {
+            if slot.colours.is_empty() {
+                output_colours.resize(output_colours.len() + slot.sample_locations.len() * 3, 0);
+            } else {
+                output_colours.extend_from_slice(&slot.colours);
+            }
+        }
+        true
+    }
+}
+
+impl CaptureSlot {
+    fn new(frame_source: Box<dyn FrameSource>, pixel_locations: Vec<[usize; 2]>) -> CaptureSlot {
+        CaptureSlot {
+            frame_source,
+            sample_locations: pixel_locations.clone(),
+            pixel_locations,
+            colours: Vec::new(),
+            downscaled_image: Vec::new()
+        }
+    }
+}
+
+fn sample_points(kernel: &Kernel, image: &[u8], width: usize, height: usize, points: &[[usize; 2]], pool: Option<&ThreadPool>, colours: &mut Vec<u8>) {
+    /*
+    Every LED is sampled independently, so with a pool the points are split into
+    one contiguous chunk per thread, each writing straight into its own part of colours
+    */
+    colours.clear();
+    let pool = match pool {
+        Some(pool) if !points.is_empty() => pool,
+        _ => {
+            for point in points {
+                colours.extend_from_slice(&kernel.kernel_pass_result(image, width, height, point[0], point[1]));
+            }
+            return;
+        }
+    };
+
+    colours.resize(points.len() * 3, 0);
+    let chunk_size = points.len().div_ceil(pool.current_num_threads());
+    pool.scope(|scope| {
+        for (point_chunk, colour_chunk) in points.chunks(chunk_size).zip(colours.chunks_mut(chunk_size * 3)) {
+            scope.spawn(move |_| {
+                for (point, colour) in point_chunk.iter().zip(colour_chunk.chunks_exact_mut(3)) {
+                    colour.copy_from_slice(&kernel.kernel_pass_result(image, width, height, point[0], point[1]));
+                }
+            });
+        }
+    });
+}
+
+fn downscale_image(image: &[u8], width: usize, height: usize, factor: usize, output: &mut Vec<u8>) -> (usize, usize) {
+    /*
+    Box filter, each output pixel is the average of a factor x factor block of BGRA pixels.
+    Leftover rows and columns that don't fill a whole block are dropped
+    */
+    let (output_width, output_height) = (width / factor, height / factor);
+    output.clear();
+    output.reserve(output_width * output_height * 4);
+    let block_area = (factor * factor) as u32;
+    let mut row_sums = vec![0_u32; output_width * 4];
+    for output_y in 0..output_height {
+        row_sums.fill(0);
+        for y in output_y * factor..(output_y + 1) * factor {
+            let row = &image[y * width * 4..(y * width + output_width * factor) * 4];
+            for (output_x, block) in row.chunks_exact(factor * 4).enumerate() {
+                let sums = &mut row_sums[output_x * 4..output_x * 4 + 4];
+                for pixel in block.chunks_exact(4) {
+                    for (sum, channel) in sums.iter_mut().zip(pixel) {
+                        *sum += *channel as u32;
+                    }
+                }
+            }
+        }
+        output.extend(row_sums.iter().map(|sum| (sum / block_area) as u8));
+    }
+    (output_width, output_height)
+}
+
+pub fn run_benchmark(settings: &FrameSourceSettings, monitor_config: &MonitorConfiguration, kernel: &Kernel, downscales: &[usize], thread_counts: &[usize], frames: usize) -> io::Result<()> {
+    /*
+    Times capturing and sampling frames from the configured frame source at each downscale factor and thread count.
+    The times are wall-clock, so they include waiting on other threads and anything else running on the machine.
+    Only calls that produced a new frame are counted, so a static image gives the steadiest numbers
+    */
+    let mut capture = MonitorCapture::open(settings, monitor_config, kernel, 1, 1)?;
+    println!("Sampling {} LEDs from {}, {} frames per run, wall-clock time per frame", capture.led_count(), settings.input, frames);
+
+    for downscale in downscales {
+        for sampling_threads in thread_counts {
+            capture.set_downscale(*downscale);
+            capture.set_sampling_threads(*sampling_threads);
+            let (mut sampled_frames, mut sampling_time) = (0, Duration::ZERO);
+            let mut output_colours = Vec::new();
+            let started = Instant::now();
+            while sampled_frames < frames && started.elapsed() < BENCHMARK_TIME_LIMIT {
+                let sample_start = Instant::now();
+                if capture.sample(&mut output_colours) {
+                    sampling_time += sample_start.elapsed();
+                    sampled_frames += 1;
+                }
+            }
+            if sampled_frames == 0 {
+                println!("downscale {}, {} threads: no frames captured", downscale, sampling_threads);
+            } else {
+                let frame_time = sampling_time.as_secs_f64() / sampled_frames as f64;
+                println!("downscale {}, {} threads: {:.3}ms per frame, {:.0} frames per second over {} frames", downscale, sampling_threads, frame_time * 1000.0, 1.0 / frame_time, sampled_frames);
+            }
+        }
+    }
+    Ok(())
+}
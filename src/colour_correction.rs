@@ -0,0 +1,113 @@
+//Adjusts the colours sent to the LEDs so the strip looks right next to the screen
+use std::fmt::{Display, Formatter};
+use json::object;
+use crate::control_server;
+
+// above this the LEDs lag too far behind the screen to be useful
+pub const MAX_SMOOTHING: f32 = 0.95;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColourSettings {
+    // applied to each channel after the other adjustments, above 1 darkens the mid tones like most LEDs need
+    pub gamma: f32,
+    // 0 is greyscale, 1 leaves the colours alone, above 1 makes them more vivid
+    pub saturation: f32,
+    // red, green and blue multipliers to match the strip's white to the screen's
+    pub white_balance: [f32; 3],
+    // fraction of the previous frame kept each frame, 0 follows the screen exactly
+    pub smoothing: f32,
+}
+
+impl Default for ColourSettings {
+    fn default() -> Self {
+        ColourSettings {
+            gamma: 1.0,
+            saturation: 1.0,
+            white_balance: [1.0, 1.0, 1.0],
+            smoothing: 0.0,
+        }
+    }
+}
+
+impl Display for ColourSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ColourSettings{{gamma: {}, saturation: {}, white_balance: {:?}, smoothing: {}}}",
+            self.gamma, self.saturation, self.white_balance, self.smoothing
+        )
+    }
+}
+
+pub struct ColourCorrection {
+    settings: ColourSettings,
+    brightness: f32,
+    // brightness, white balance and gamma for every value of each channel
+    lookup: [[u8; 256]; 3],
+    // the last frame before the lookup, kept as floats so slow fades don't get stuck on rounding
+    smoothed: Vec<f32>,
+}
+
+impl ColourCorrection {
+    pub fn new(settings: ColourSettings, brightness: f32) -> ColourCorrection {
+        let mut correction = ColourCorrection {
+            settings,
+            brightness,
+            lookup: [[0; 256]; 3],
+            smoothed: Vec::new(),
+        };
+        correction.build_lookup();
+        correction
+    }
+
+    pub fn set_settings(&mut self, settings: ColourSettings) {
+        self.settings = settings;
+        self.build_lookup();
+    }
+
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness;
+        self.build_lookup();
+    }
+
+    fn build_lookup(&mut self) {
+        let gamma = self.settings.gamma.max(0.01);
+        for (channel, lookup) in self.lookup.iter_mut().enumerate() {
+            let scale = self.brightness * self.settings.white_balance[channel].max(0.0);
+            for (value, corrected) in lookup.iter_mut().enumerate() {
+                *corrected = ((value as f32 / 255.0).powf(gamma) * scale * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    pub fn apply(&mut self, colours: &mut [u8]) {
+        let saturation = self.settings.saturation.max(0.0);
+        let smoothing = self.settings.smoothing.clamp(0.0, MAX_SMOOTHING);
+        if self.smoothed.len() != colours.len() {
+            // the number of LEDs changed so there's nothing to smooth from
+            self.smoothed = colours.iter().map(|colour| *colour as f32).collect();
+        }
+        for (colour, smoothed) in colours.chunks_exact_mut(3).zip(self.smoothed.chunks_exact_mut(3)) {
+            let (r, g, b) = (colour[0] as f32, colour[1] as f32, colour[2] as f32);
+            let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+            for (channel, value) in [r, g, b].iter().enumerate() {
+                let saturated = (luma + (value - luma) * saturation).clamp(0.0, 255.0);
+                smoothed[channel] = smoothed[channel] * smoothing + saturated * (1.0 - smoothing);
+                colour[channel] = self.lookup[channel][smoothed[channel].round() as usize];
+            }
+        }
+    }
+}
+
+pub fn send_colours_to_tray(brightness: f32, settings: &ColourSettings) -> bool {
+    // the settings window changes the running lights through the tray's control server while sliders are dragged
+    control_server::post_to_tray("/api/colour_settings", object!{
+        "brightness": brightness,
+        "gamma": settings.gamma,
+        "saturation": settings.saturation,
+        "white_balance_red": settings.white_balance[0],
+        "white_balance_green": settings.white_balance[1],
+        "white_balance_blue": settings.white_balance[2],
+        "smoothing": settings.smoothing,
+    })
+}
@@ -0,0 +1,338 @@
+//Optional HTTP server that exposes the tray controls over the network and streams the LED colours
+extern crate tiny_http;
+extern crate tungstenite;
+
+use std::{env, io, thread, time};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex, mpsc};
+use std::sync::mpsc::Sender;
+use json::{object, JsonValue};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+use crate::calibration;
+use crate::colour_correction::ColourSettings;
+use crate::effects::{self, EffectKind};
+use crate::multiplexer::{InputContent, InputSource, CONTROL_API_PRIORITY};
+use crate::worker::{ControlMessage, OutputMode, WorkerStatus};
+
+const REMOTE_PAGE: &str = include_str!("../assets/remote.html");
+// set by the tray for the windows it launches so they can send their changes to its control server,
+// windows opened from a terminal have nobody to tell
+pub const TRAY_ADDRESS_VARIABLE: &str = "MONITOR_AMBIENT_LIGHTING_CONTROL_ADDRESS";
+// a window gives up on the tray after this long rather than freezing with it
+const CLIENT_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+pub fn spawn_control_server(address: &str, worker_controller: Arc<Mutex<Sender<ControlMessage>>>, quit_sender: Sender<()>) -> Option<SocketAddr> {
+    /*
+    Returns the address to reach the server on from this machine,
+    an address like 0.0.0.0:0 is only known once the server is listening.
+    /api/quit sends on quit_sender for whatever is running the program to shut it down its usual way
+    */
+    let server = match Server::http(address) {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("Unable to start control server on {}: {}", address, error);
+            return None
+        }
+    };
+    let local_address = server.server_addr().to_ip().map(|mut local_address| {
+        if local_address.ip().is_unspecified() {
+            local_address.set_ip(if local_address.is_ipv4() { Ipv4Addr::LOCALHOST.into() } else { Ipv6Addr::LOCALHOST.into() });
+        }
+        local_address
+    });
+    match local_address {
+        Some(local_address) => println!("Control server listening on http://{}", local_address),
+        None => println!("Control server listening on http://{}", address)
+    }
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle_request(request, &worker_controller, &quit_sender);
+        }
+    });
+    local_address
+}
+
+pub fn post_to_tray(path: &str, body: JsonValue) -> bool {
+    // false when the window wasn't launched from the tray or the tray didn't take the request
+    let address = match env::var(TRAY_ADDRESS_VARIABLE) {
+        Ok(address) => address,
+        Err(_) => return false
+    };
+    match post(address.as_str(), path, body.dump().as_str()) {
+        Ok(true) => true,
+        Ok(false) => {
+            eprintln!("The tray refused {}", path);
+            false
+        },
+        Err(error) => {
+            eprintln!("Unable to reach the tray on {}: {}", address, error);
+            false
+        }
+    }
+}
+
+fn post(address: &str, path: &str, body: &str) -> io::Result<bool> {
+    let address: SocketAddr = address.parse().map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    let mut stream = TcpStream::connect_timeout(&address, CLIENT_TIMEOUT)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, address, body.len(), body
+    )?;
+    stream.flush()?;
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    Ok(status_line.split_whitespace().nth(1) == Some("200"))
+}
+
+fn handle_request(mut request: Request, worker_controller: &Arc<Mutex<Sender<ControlMessage>>>, quit_sender: &Sender<()>) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+
+    let result = match (method, url.as_str()) {
+        (Method::Get, "/") => {
+            request.respond(Response::from_string(REMOTE_PAGE).with_header(header("Content-Type", "text/html; charset=utf-8")))
+        },
+        (Method::Get, "/api/status") => {
+            match request_status(worker_controller) {
+                Some(status) => respond_json(request, 200, status_to_json(&status)),
+                None => respond_json(request, 503, object!{"error": "Worker is not running"})
+            }
+        },
+        (Method::Get, "/api/stream") => {
+            stream_output(request, worker_controller);
+            Ok(())
+        },
+        (Method::Post, "/api/reload") => {
+            send_control_message(worker_controller, ControlMessage::UpdateConfiguration);
+            respond_json(request, 200, object!{"ok": true})
+        },
+        (Method::Post, "/api/pause") => {
+            send_control_message(worker_controller, ControlMessage::Pause);
+            respond_json(request, 200, object!{"ok": true})
+        },
+        (Method::Post, "/api/resume") => {
+            send_control_message(worker_controller, ControlMessage::Resume);
+            respond_json(request, 200, object!{"ok": true})
+        },
+        (Method::Post, "/api/off") => {
+            send_control_message(worker_controller, ControlMessage::Off);
+            respond_json(request, 200, object!{"ok": true})
+        },
+        (Method::Post, "/api/brightness") => {
+            let brightness = read_json_body(&mut request).and_then(|body| body["brightness"].as_f32());
+            match brightness {
+                Some(brightness) => {
+                    send_control_message(worker_controller, ControlMessage::SetBrightness(brightness));
+                    respond_json(request, 200, object!{"ok": true})
+                },
+                None => respond_json(request, 400, object!{"error": "Expected a body like {\"brightness\": 0.5}"})
+            }
+        },
+        (Method::Post, "/api/mode") => {
+            let output_mode = read_json_body(&mut request).and_then(|body| body["mode"].as_str().and_then(OutputMode::from_name));
+            match output_mode {
+                Some(output_mode) => {
+                    send_control_message(worker_controller, ControlMessage::SetOutputMode(output_mode));
+                    respond_json(request, 200, object!{"ok": true})
+                },
+                None => respond_json(request, 400, object!{"error": "Expected a body like {\"mode\": \"rainbow_swirl\"}"})
+            }
+        },
+        (Method::Post, "/api/colour") => {
+            let body = read_json_body(&mut request).unwrap_or(JsonValue::Null);
+            let content = if body["colours"].is_array() {
+                parse_channels(&body["colours"]).map(InputContent::Colours)
+            } else {
+                body["colour"].as_str().and_then(effects::parse_colour).map(InputContent::SolidColour)
+            };
+            match content {
+                Some(content) => match send_input(worker_controller, &body, content) {
+                    Ok(()) => respond_json(request, 200, object!{"ok": true}),
+                    Err(error) => respond_json(request, 400, object!{"error": error})
+                },
+                None => respond_json(request, 400, object!{"error": "Expected a body like {\"colour\": \"#ff0000\", \"priority\": 200, \"timeout\": 5} or {\"colours\": [255, 0, 0]} with channels from 0 to 255"})
+            }
+        },
+        (Method::Post, "/api/effect") => {
+            let body = read_json_body(&mut request).unwrap_or(JsonValue::Null);
+            match body["effect"].as_str().and_then(EffectKind::from_name) {
+                Some(kind) => match send_input(worker_controller, &body, InputContent::Effect(kind)) {
+                    Ok(()) => respond_json(request, 200, object!{"ok": true}),
+                    Err(error) => respond_json(request, 400, object!{"error": error})
+                },
+                None => respond_json(request, 400, object!{"error": "Expected a body like {\"effect\": \"breathing\", \"priority\": 200, \"timeout\": 5}"})
+            }
+        },
+        (Method::Post, "/api/colour_settings") => {
+            // what the settings window sends while its sliders are dragged, only saving it keeps it
+            match read_json_body(&mut request).and_then(|body| parse_colour_settings(&body)) {
+                Some((brightness, colour_settings)) => {
+                    send_control_message(worker_controller, ControlMessage::SetBrightness(brightness));
+                    send_control_message(worker_controller, ControlMessage::SetColourSettings(colour_settings));
+                    respond_json(request, 200, object!{"ok": true})
+                },
+                None => respond_json(request, 400, object!{"error": "Expected a body like {\"brightness\": 1, \"gamma\": 1, \"saturation\": 1, \"white_balance_red\": 1, \"white_balance_green\": 1, \"white_balance_blue\": 1, \"smoothing\": 0}"})
+            }
+        },
+        (Method::Post, "/api/identify") => {
+            // lights the LEDs over everything else until a body without colours stops it
+            let body = read_json_body(&mut request).unwrap_or(JsonValue::Null);
+            let colours = if body["colours"].is_null() { Some(None) } else { parse_channels(&body["colours"]).map(Some) };
+            match colours {
+                Some(colours) => {
+                    send_control_message(worker_controller, calibration::identify_message(colours));
+                    respond_json(request, 200, object!{"ok": true})
+                },
+                None => respond_json(request, 400, object!{"error": "Expected a body like {\"colours\": [255, 0, 0]} or {} to stop"})
+            }
+        },
+        (Method::Post, "/api/clear") => {
+            send_control_message(worker_controller, ControlMessage::ClearInput(InputSource::ControlApi));
+            respond_json(request, 200, object!{"ok": true})
+        },
+        (Method::Post, "/api/quit") => {
+            // answered first, quitting stops the server too
+            let result = respond_json(request, 200, object!{"ok": true});
+            let _ = quit_sender.send(());
+            result
+        },
+        _ => respond_json(request, 404, object!{"error": "Not found"})
+    };
+
+    if let Err(error) = result {
+        eprintln!("Failed to respond to control request {}: {}", url, error);
+    }
+}
+
+fn stream_output(request: Request, worker_controller: &Arc<Mutex<Sender<ControlMessage>>>) {
+    let websocket_key = request.headers().iter()
+        .find(|request_header| request_header.field.equiv("Sec-WebSocket-Key"))
+        .map(|request_header| request_header.value.as_str().to_string());
+    let websocket_key = match websocket_key {
+        Some(key) => key,
+        None => {
+            let _ = respond_json(request, 400, object!{"error": "Expected a websocket upgrade"});
+            return
+        }
+    };
+
+    // a single slot buffer means a slow client only ever receives the most recent frames
+    let (frame_sender, frame_receiver) = mpsc::sync_channel(1);
+    if !send_control_message(worker_controller, ControlMessage::SubscribeOutput(frame_sender)) {
+        let _ = respond_json(request, 503, object!{"error": "Worker is not running"});
+        return
+    }
+
+    let response = Response::new_empty(StatusCode(101))
+        .with_header(header("Upgrade", "websocket"))
+        .with_header(header("Connection", "Upgrade"))
+        .with_header(header("Sec-WebSocket-Accept", derive_accept_key(websocket_key.as_bytes()).as_str()));
+    let stream = request.upgrade("websocket", response);
+
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        for colours in frame_receiver {
+            if socket.write_message(Message::Binary(colours.to_vec())).is_err() {
+                // dropping the receiver unsubscribes from the worker
+                break
+            }
+        }
+    });
+}
+
+fn send_input(worker_controller: &Arc<Mutex<Sender<ControlMessage>>>, body: &JsonValue, content: InputContent) -> Result<(), &'static str> {
+    // without a timeout the input stays until it's cleared or replaced
+    let timeout = match body["timeout"].as_f32() {
+        Some(seconds) => Some(time::Duration::try_from_secs_f32(seconds.max(0.0)).map_err(|_| "The timeout is too long")?),
+        None => None
+    };
+    send_control_message(worker_controller, ControlMessage::SetInput{
+        source: InputSource::ControlApi,
+        priority: body["priority"].as_u8().unwrap_or(CONTROL_API_PRIORITY),
+        content,
+        timeout
+    });
+    Ok(())
+}
+
+fn parse_channels(colours: &JsonValue) -> Option<Vec<u8>> {
+    // every channel has to be a whole number from 0 to 255, anything else refuses the whole list
+    if !colours.is_array() {
+        return None
+    }
+    colours.members().map(JsonValue::as_u8).collect()
+}
+
+fn parse_colour_settings(body: &JsonValue) -> Option<(f32, ColourSettings)> {
+    // the same names as the configuration file
+    Some((body["brightness"].as_f32()?, ColourSettings {
+        gamma: body["gamma"].as_f32()?,
+        saturation: body["saturation"].as_f32()?,
+        white_balance: [
+            body["white_balance_red"].as_f32()?,
+            body["white_balance_green"].as_f32()?,
+            body["white_balance_blue"].as_f32()?,
+        ],
+        smoothing: body["smoothing"].as_f32()?,
+    }))
+}
+
+fn read_json_body(request: &mut Request) -> Option<JsonValue> {
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body).ok()?;
+    json::parse(body.as_str()).ok()
+}
+
+fn request_status(worker_controller: &Arc<Mutex<Sender<ControlMessage>>>) -> Option<WorkerStatus> {
+    let (reply_sender, reply_receiver) = mpsc::channel();
+    if !send_control_message(worker_controller, ControlMessage::RequestStatus(reply_sender)) {
+        return None
+    }
+    reply_receiver.recv_timeout(time::Duration::from_secs(2)).ok()
+}
+
+fn send_control_message(worker_controller: &Arc<Mutex<Sender<ControlMessage>>>, message: ControlMessage) -> bool {
+    worker_controller.lock().unwrap().send(message).is_ok()
+}
+
+fn status_to_json(status: &WorkerStatus) -> JsonValue {
+    let mut pixel_locations = JsonValue::new_array();
+    for location in &status.pixel_locations {
+        let _ = pixel_locations.push(json::array![location[0], location[1]]);
+    }
+    object!{
+        "state": status.state.to_string(),
+        "output_mode": status.output_mode.name(),
+        "active_input": status.active_input.map(|(source, _priority)| source.to_string()),
+        "active_priority": status.active_input.map(|(_source, priority)| priority),
+        "blank_on_exit": status.blank_on_exit,
+        "brightness": status.brightness,
+        "screen_width": status.screen_width,
+        "screen_height": status.screen_height,
+        "led_count": status.pixel_locations.len(),
+        "sampling_time_ms": status.sampling_time.as_secs_f64() * 1000.0,
+        "frame_rate": status.frame_rate,
+        "output": status.output.as_str(),
+        "error": status.error.clone(),
+        "pixel_locations": pixel_locations
+    }
+}
+
+fn respond_json(request: Request, status_code: u16, body: JsonValue) -> std::io::Result<()> {
+    request.respond(
+        Response::from_string(body.dump())
+            .with_status_code(StatusCode(status_code))
+            .with_header(header("Content-Type", "application/json"))
+    )
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+}
@@ -0,0 +1,201 @@
+//Generated lighting effects that produce the same per LED colour vector as screen capture
+use std::f32::consts::TAU;
+use std::fmt::{Display, Formatter};
+use std::time::Instant;
+use crate::side::Side;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectKind {
+    StaticColour,
+    StaticGradient,
+    RainbowSwirl,
+    Breathing,
+    ColourCycle,
+}
+
+impl EffectKind {
+    pub(crate) const ALL: [EffectKind; 5] = [
+        EffectKind::StaticColour,
+        EffectKind::StaticGradient,
+        EffectKind::RainbowSwirl,
+        EffectKind::Breathing,
+        EffectKind::ColourCycle
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectKind::StaticColour => "static_colour",
+            EffectKind::StaticGradient => "static_gradient",
+            EffectKind::RainbowSwirl => "rainbow_swirl",
+            EffectKind::Breathing => "breathing",
+            EffectKind::ColourCycle => "colour_cycle"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EffectKind> {
+        EffectKind::ALL.iter().find(|kind| kind.name() == name).copied()
+    }
+}
+
+impl Display for EffectKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                EffectKind::StaticColour => "Static Colour",
+                EffectKind::StaticGradient => "Gradient",
+                EffectKind::RainbowSwirl => "Rainbow Swirl",
+                EffectKind::Breathing => "Breathing",
+                EffectKind::ColourCycle => "Colour Cycle"
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EffectSettings {
+    // used by static colour and breathing, and as the start of each side's gradient
+    pub colour: [u8; 3],
+    // the end of each side's gradient
+    pub secondary_colour: [u8; 3],
+    // multiplier on how fast the animated effects move
+    pub speed: f32,
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        EffectSettings {
+            colour: colour_temperature(6500.0),
+            secondary_colour: [0, 0, 255],
+            speed: 1.0
+        }
+    }
+}
+
+pub struct LedPlacement {
+    // position on the screen with 0,0 at the top left and 1,1 at the bottom right
+    pub position: [f32; 2],
+    // how far along its side the LED is in chain order, from 0 to 1
+    pub side_progress: f32,
+}
+
+pub fn place_leds(pixel_locations: &[[usize; 2]], led_sides: &[(Side, f32)], screen_width: usize, screen_height: usize) -> Vec<LedPlacement> {
+    pixel_locations.iter().zip(led_sides.iter()).map(|(location, (_side, side_progress))| {
+        LedPlacement {
+            position: [location[0] as f32 / screen_width.max(1) as f32, location[1] as f32 / screen_height.max(1) as f32],
+            side_progress: *side_progress
+        }
+    }).collect()
+}
+
+pub struct EffectEngine {
+    pub settings: EffectSettings,
+    start_time: Instant,
+}
+
+impl EffectEngine {
+    pub fn new(settings: EffectSettings) -> EffectEngine {
+        EffectEngine {
+            settings,
+            start_time: Instant::now()
+        }
+    }
+
+    pub fn render(&self, kind: EffectKind, leds: &[LedPlacement], output: &mut Vec<u8>) {
+        // appends each LED's colour to output, which the caller reuses between frames
+        let elapsed = self.start_time.elapsed().as_secs_f32() * self.settings.speed;
+
+        for led in leds {
+            let colour = match kind {
+                EffectKind::StaticColour => self.settings.colour,
+                EffectKind::StaticGradient => blend(self.settings.colour, self.settings.secondary_colour, led.side_progress),
+                EffectKind::RainbowSwirl => {
+                    // hue follows the angle around the centre of the screen and rotates once every 10 seconds
+                    let angle = (led.position[1] - 0.5).atan2(led.position[0] - 0.5);
+                    hsv_to_rgb((angle / TAU + elapsed * 0.1).rem_euclid(1.0), 1.0, 1.0)
+                },
+                EffectKind::Breathing => {
+                    // one breath every 4 seconds
+                    let intensity = (1.0 - (elapsed * TAU / 4.0).cos()) / 2.0;
+                    blend([0, 0, 0], self.settings.colour, intensity)
+                },
+                EffectKind::ColourCycle => hsv_to_rgb((elapsed * 0.05).rem_euclid(1.0), 1.0, 1.0)
+            };
+            output.extend_from_slice(&colour);
+        }
+    }
+}
+
+fn blend(start: [u8; 3], end: [u8; 3], amount: f32) -> [u8; 3] {
+    let amount = amount.clamp(0.0, 1.0);
+    std::array::from_fn(|channel| (start[channel] as f32 + (end[channel] as f32 - start[channel] as f32) * amount).round() as u8)
+}
+
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [u8; 3] {
+    /*
+    hue, saturation and value are all between 0 and 1
+    */
+    let sector = hue * 6.0;
+    let chroma = value * saturation;
+    let secondary = chroma * (1.0 - ((sector % 2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, secondary, 0.0),
+        1 => (secondary, chroma, 0.0),
+        2 => (0.0, chroma, secondary),
+        3 => (0.0, secondary, chroma),
+        4 => (secondary, 0.0, chroma),
+        _ => (chroma, 0.0, secondary)
+    };
+    let minimum = value - chroma;
+    [((r + minimum) * 255.0) as u8, ((g + minimum) * 255.0) as u8, ((b + minimum) * 255.0) as u8]
+}
+
+pub fn colour_temperature(kelvin: f32) -> [u8; 3] {
+    /*
+    Approximates the colour of a black body at the given temperature, valid from 1000K to 40000K
+    Based on Tanner Helland's curve fit of the CIE 1964 colour matching data, with the constants cut down to what an f32 holds
+    */
+    let temperature = kelvin.clamp(1000.0, 40000.0) / 100.0;
+    let red = if temperature <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (temperature - 60.0).powf(-0.13320476)
+    };
+    let green = if temperature <= 66.0 {
+        99.4708 * temperature.ln() - 161.11957
+    } else {
+        288.12216 * (temperature - 60.0).powf(-0.075514846)
+    };
+    let blue = if temperature >= 66.0 {
+        255.0
+    } else if temperature <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (temperature - 10.0).ln() - 305.0448
+    };
+    [red.clamp(0.0, 255.0) as u8, green.clamp(0.0, 255.0) as u8, blue.clamp(0.0, 255.0) as u8]
+}
+
+pub fn parse_colour(text: &str) -> Option<[u8; 3]> {
+    /*
+    Accepts hex colours like #ff8800 or ff8800, or a colour temperature like 6500K
+    */
+    let text = text.trim();
+    if let Some(kelvin) = text.strip_suffix('K').or_else(|| text.strip_suffix('k')) {
+        return Some(colour_temperature(kelvin.parse().ok()?));
+    }
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 {
+        return None;
+    }
+    Some([
+        u8::from_str_radix(hex.get(0..2)?, 16).ok()?,
+        u8::from_str_radix(hex.get(2..4)?, 16).ok()?,
+        u8::from_str_radix(hex.get(4..6)?, 16).ok()?
+    ])
+}
+
+pub fn format_colour(colour: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", colour[0], colour[1], colour[2])
+}
@@ -0,0 +1,95 @@
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::frame_source::{Frame, FrameSource};
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "ppm", "pnm", "jpg", "jpeg", "bmp"];
+
+pub struct ImageSequenceSource {
+    // a single image is a sequence of one frame
+    paths: Vec<PathBuf>,
+    frame_rate: f32,
+    started: Instant,
+    current_frame: Option<usize>,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl ImageSequenceSource {
+    pub fn open(path: &str, frame_rate: f32) -> io::Result<ImageSequenceSource> {
+        /*
+        Frames in a directory are played in file name order so they should be numbered with leading zeros,
+        every frame has to be the same size as the first
+        */
+        let path = Path::new(path);
+        let paths = if path.is_dir() {
+            let mut paths: Vec<PathBuf> = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())))
+                .collect();
+            paths.sort();
+            paths
+        } else {
+            vec![path.to_path_buf()]
+        };
+        if paths.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("No images found in {}", path.display())));
+        }
+
+        let mut source = ImageSequenceSource {
+            paths,
+            frame_rate: frame_rate.max(0.001),
+            started: Instant::now(),
+            current_frame: None,
+            width: 0,
+            height: 0,
+            pixels: Vec::new()
+        };
+        source.load_frame(0)?;
+        Ok(source)
+    }
+
+    fn load_frame(&mut self, frame_number: usize) -> io::Result<()> {
+        let path = &self.paths[frame_number];
+        let image = image::open(path)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("Unable to load {}: {}", path.display(), error)))?
+            .to_rgba8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        if self.current_frame.is_some() && (width, height) != (self.width, self.height) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is {}x{} but the sequence is {}x{}", path.display(), width, height, self.width, self.height)));
+        }
+
+        // swap to BGRA to match screen captures
+        let mut pixels = image.into_raw();
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        self.pixels = pixels;
+        self.width = width;
+        self.height = height;
+        self.current_frame = Some(frame_number);
+        Ok(())
+    }
+}
+
+impl FrameSource for ImageSequenceSource {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn frame(&mut self) -> io::Result<Frame<'_>> {
+        // loops back to the first frame after the last one
+        let frame_number = (self.started.elapsed().as_secs_f32() * self.frame_rate) as usize % self.paths.len();
+        if self.current_frame != Some(frame_number) {
+            self.load_frame(frame_number)?;
+        }
+        Ok(Frame::Buffer(&self.pixels))
+    }
+}
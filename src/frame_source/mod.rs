@@ -0,0 +1,98 @@
+//Where the worker gets the images it samples LED colours from, the screen or something standing in for it
+pub mod image_file;
+pub mod raw_video;
+pub mod test_pattern;
+
+use std::io;
+use std::ops::Deref;
+use crate::kernel::Kernel;
+use crate::monitor_config::Monitor;
+use self::image_file::ImageSequenceSource;
+use self::raw_video::{PixelFormat, RawVideoSource};
+use self::test_pattern::{TestPattern, TestPatternSource};
+
+pub trait FrameSource {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    // BGRA pixels row by row, the same layout scrap captures in.
+    // Fails with WouldBlock when no new frame is ready yet
+    fn frame(&mut self) -> io::Result<Frame<'_>>;
+}
+
+pub enum Frame<'a> {
+    Screen(scrap::Frame<'a>),
+    Buffer(&'a [u8]),
+}
+
+impl Deref for Frame<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Frame::Screen(frame) => frame,
+            Frame::Buffer(buffer) => buffer
+        }
+    }
+}
+
+impl FrameSource for scrap::Capturer {
+    fn width(&self) -> usize {
+        scrap::Capturer::width(self)
+    }
+
+    fn height(&self) -> usize {
+        scrap::Capturer::height(self)
+    }
+
+    fn frame(&mut self) -> io::Result<Frame<'_>> {
+        scrap::Capturer::frame(self).map(Frame::Screen)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSourceSettings {
+    // "screen" to capture the monitor, "pattern:<name>" for a test pattern, "raw:<path>" or "y4m:<path>" for video
+    // (with - as the path for stdin), otherwise the path of an image or a directory of numbered frames
+    pub input: String,
+    // frames per second frames, patterns and raw video are played back at, y4m streams carry their own
+    pub frame_rate: f32,
+    // resolution test patterns are generated at and raw video frames are read at
+    pub width: usize,
+    pub height: usize,
+    pub pixel_format: PixelFormat,
+}
+
+impl Default for FrameSourceSettings {
+    fn default() -> Self {
+        FrameSourceSettings {
+            input: String::from("screen"),
+            frame_rate: 30.0,
+            width: 1920,
+            height: 1080,
+            pixel_format: PixelFormat::default()
+        }
+    }
+}
+
+pub fn open_frame_source(settings: &FrameSourceSettings, monitor: &Monitor, kernel: &Kernel) -> io::Result<Box<dyn FrameSource>> {
+    if let Some(pattern_name) = settings.input.strip_prefix("pattern:") {
+        let pattern = TestPattern::from_name(pattern_name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown test pattern {}", pattern_name)))?;
+        return Ok(Box::new(TestPatternSource::new(pattern, settings.width.max(1), settings.height.max(1), settings.frame_rate, monitor, kernel)));
+    }
+    if let Some(path) = settings.input.strip_prefix("raw:") {
+        return Ok(Box::new(RawVideoSource::open_raw(path, settings.width.max(1), settings.height.max(1), settings.pixel_format, settings.frame_rate)?));
+    }
+    if let Some(path) = settings.input.strip_prefix("y4m:") {
+        return Ok(Box::new(RawVideoSource::open_y4m(path, settings.frame_rate)?));
+    }
+    match settings.input.as_str() {
+        "screen" => {
+            let display = scrap::Display::all()?.into_iter().nth(monitor.monitor_number.saturating_sub(1))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Monitor {} not found", monitor.monitor_number)))?;
+            Ok(Box::new(scrap::Capturer::new(display)?))
+        },
+        path => Ok(Box::new(ImageSequenceSource::open(path, settings.frame_rate)?))
+    }
+}
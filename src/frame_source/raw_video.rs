@@ -0,0 +1,331 @@
+use std::{fs, io, thread};
+use std::io::{BufRead, BufReader};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+use crate::frame_source::{Frame, FrameSource};
+
+// decoded frames waiting for the worker, the reader blocks once this many are queued
+const FRAME_BUFFER_SIZE: usize = 2;
+const Y4M_SIGNATURE: &str = "YUV4MPEG2";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    Bgra,
+    Rgba,
+    Rgb,
+}
+
+impl PixelFormat {
+    pub(crate) const ALL: [PixelFormat; 3] = [
+        PixelFormat::Bgra,
+        PixelFormat::Rgba,
+        PixelFormat::Rgb
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PixelFormat::Bgra => "bgra",
+            PixelFormat::Rgba => "rgba",
+            PixelFormat::Rgb => "rgb"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PixelFormat> {
+        PixelFormat::ALL.iter().find(|format| format.name() == name).copied()
+    }
+
+    fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Bgra | PixelFormat::Rgba => 4,
+            PixelFormat::Rgb => 3
+        }
+    }
+}
+
+impl Default for PixelFormat {
+    fn default() -> Self {
+        // what scrap captures and ffmpeg's -pix_fmt bgra produces, so frames pass through untouched
+        PixelFormat::Bgra
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ChromaSubsampling {
+    C420,
+    C422,
+    C444,
+    Mono,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum VideoFormat {
+    Raw(PixelFormat),
+    Y4m(ChromaSubsampling),
+}
+
+impl VideoFormat {
+    fn frame_size(&self, width: usize, height: usize) -> usize {
+        match self {
+            VideoFormat::Raw(pixel_format) => width * height * pixel_format.bytes_per_pixel(),
+            VideoFormat::Y4m(chroma) => {
+                let (chroma_width, chroma_height) = chroma_plane_size(*chroma, width, height);
+                width * height + chroma_width * chroma_height * 2
+            }
+        }
+    }
+}
+
+struct Y4mHeader {
+    width: usize,
+    height: usize,
+    frame_rate: Option<f32>,
+    chroma: ChromaSubsampling,
+}
+
+pub struct RawVideoSource {
+    width: usize,
+    height: usize,
+    receiver: Receiver<Vec<u8>>,
+    pixels: Vec<u8>,
+}
+
+impl RawVideoSource {
+    pub fn open_raw(path: &str, width: usize, height: usize, pixel_format: PixelFormat, frame_rate: f32) -> io::Result<RawVideoSource> {
+        /*
+        Frames are width * height pixels back to back with no header, like ffmpeg -f rawvideo writes
+        */
+        let reader = open_reader(path)?;
+        Ok(RawVideoSource::start(path, reader, width, height, VideoFormat::Raw(pixel_format), frame_rate))
+    }
+
+    pub fn open_y4m(path: &str, default_frame_rate: f32) -> io::Result<RawVideoSource> {
+        /*
+        The size, frame rate and chroma subsampling come from the stream header
+        */
+        let mut reader = open_reader(path)?;
+        let header = read_y4m_header(reader.as_mut())?;
+        let frame_rate = header.frame_rate.unwrap_or(default_frame_rate);
+        Ok(RawVideoSource::start(path, reader, header.width, header.height, VideoFormat::Y4m(header.chroma), frame_rate))
+    }
+
+    fn start(path: &str, mut reader: Box<dyn BufRead + Send>, width: usize, height: usize, format: VideoFormat, frame_rate: f32) -> RawVideoSource {
+        let (sender, receiver) = mpsc::sync_channel(FRAME_BUFFER_SIZE);
+        let path = path.to_string();
+        // only regular files can be played again from the start, pipes and stdin end when the writer closes them
+        let can_loop = path != "-" && fs::metadata(&path).is_ok_and(|metadata| metadata.is_file());
+        let frame_duration = Duration::from_secs_f32(1.0 / frame_rate.max(0.001));
+
+        thread::spawn(move || {
+            let mut frame_data = vec![0; format.frame_size(width, height)];
+            let mut next_frame_time = Instant::now();
+            // a file without a whole frame in it would otherwise be reopened over and over as fast as it can be read
+            let mut read_since_restart = false;
+            loop {
+                match read_frame(reader.as_mut(), format, &mut frame_data) {
+                    Ok(_) => read_since_restart = true,
+                    Err(error) if error.kind() == io::ErrorKind::UnexpectedEof && can_loop => {
+                        if !read_since_restart {
+                            eprintln!("Video input stopped: {} doesn't have a whole frame in it", path);
+                            break
+                        }
+                        read_since_restart = false;
+                        match reopen(&path, format) {
+                            Ok(new_reader) => {
+                                reader = new_reader;
+                                continue
+                            },
+                            Err(error) => {
+                                eprintln!("Unable to restart video {}: {}", path, error);
+                                break
+                            }
+                        }
+                    },
+                    Err(error) => {
+                        eprintln!("Video input stopped: {}", error);
+                        break
+                    }
+                }
+
+                let now = Instant::now();
+                if next_frame_time > now {
+                    thread::sleep(next_frame_time - now);
+                } else if now - next_frame_time > frame_duration {
+                    // fell behind (the worker was paused or the writer stalled), carry on from now instead of rushing to catch up
+                    next_frame_time = now;
+                }
+                next_frame_time += frame_duration;
+
+                if sender.send(convert_to_bgra(format, &frame_data, width, height)).is_err() {
+                    // the source has been dropped
+                    break
+                }
+            }
+        });
+
+        RawVideoSource {
+            width,
+            height,
+            receiver,
+            pixels: Vec::new()
+        }
+    }
+}
+
+impl FrameSource for RawVideoSource {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn frame(&mut self) -> io::Result<Frame<'_>> {
+        match self.receiver.try_recv() {
+            Ok(frame) => {
+                self.pixels = frame;
+                Ok(Frame::Buffer(&self.pixels))
+            },
+            Err(TryRecvError::Empty) => Err(io::Error::new(io::ErrorKind::WouldBlock, "No new video frame yet")),
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Video input has ended"))
+        }
+    }
+}
+
+fn open_reader(path: &str) -> io::Result<Box<dyn BufRead + Send>> {
+    // "-" reads from stdin, anything else is a file or a named pipe
+    if path == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
+    } else {
+        Ok(Box::new(BufReader::new(fs::File::open(path)?)))
+    }
+}
+
+fn reopen(path: &str, format: VideoFormat) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader = open_reader(path)?;
+    if let VideoFormat::Y4m(_) = format {
+        read_y4m_header(reader.as_mut())?;
+    }
+    Ok(reader)
+}
+
+fn read_line(reader: &mut dyn BufRead) -> io::Result<String> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+    if line.last() != Some(&b'\n') {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Video ended part way through a header"));
+    }
+    line.pop();
+    String::from_utf8(line).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn read_y4m_header(reader: &mut dyn BufRead) -> io::Result<Y4mHeader> {
+    let header_line = read_line(reader)?;
+    let mut parameters = header_line.split(' ');
+    if parameters.next() != Some(Y4M_SIGNATURE) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a y4m stream"));
+    }
+
+    let mut header = Y4mHeader {
+        width: 0,
+        height: 0,
+        frame_rate: None,
+        chroma: ChromaSubsampling::C420
+    };
+    for parameter in parameters.filter(|parameter| !parameter.is_empty()) {
+        // the tag is the first character, which may not be a single byte in a corrupt header
+        let mut characters = parameter.chars();
+        let tag = characters.next();
+        let value = characters.as_str();
+        match tag {
+            Some('W') => header.width = value.parse().map_err(|_| invalid_header(parameter))?,
+            Some('H') => header.height = value.parse().map_err(|_| invalid_header(parameter))?,
+            Some('F') => {
+                let (numerator, denominator) = value.split_once(':').ok_or_else(|| invalid_header(parameter))?;
+                let (numerator, denominator): (f32, f32) = (numerator.parse().map_err(|_| invalid_header(parameter))?, denominator.parse().map_err(|_| invalid_header(parameter))?);
+                if numerator > 0.0 && denominator > 0.0 {
+                    header.frame_rate = Some(numerator / denominator);
+                }
+            },
+            Some('C') => {
+                // only 8 bit samples can be read, higher bit depths like 420p10 or mono16 are two bytes a sample
+                header.chroma = match value {
+                    "420" | "420jpeg" | "420mpeg2" | "420paldv" => ChromaSubsampling::C420,
+                    "422" => ChromaSubsampling::C422,
+                    "444" => ChromaSubsampling::C444,
+                    "mono" => ChromaSubsampling::Mono,
+                    _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported y4m colour space {}", value)))
+                };
+            },
+            // interlacing, aspect ratio and comments don't change how frames are read
+            _ => {}
+        }
+    }
+    if header.width == 0 || header.height == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "y4m header is missing the frame size"));
+    }
+    Ok(header)
+}
+
+fn invalid_header(parameter: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid y4m header parameter {}", parameter))
+}
+
+fn read_frame(reader: &mut dyn BufRead, format: VideoFormat, frame_data: &mut [u8]) -> io::Result<()> {
+    if let VideoFormat::Y4m(_) = format {
+        // each frame starts with its own FRAME line, any parameters on it are ignored
+        let frame_line = read_line(reader)?;
+        if !frame_line.starts_with("FRAME") {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected a y4m FRAME header"));
+        }
+    }
+    reader.read_exact(frame_data)
+}
+
+fn chroma_plane_size(chroma: ChromaSubsampling, width: usize, height: usize) -> (usize, usize) {
+    match chroma {
+        ChromaSubsampling::C420 => (width.div_ceil(2), height.div_ceil(2)),
+        ChromaSubsampling::C422 => (width.div_ceil(2), height),
+        ChromaSubsampling::C444 => (width, height),
+        ChromaSubsampling::Mono => (0, 0)
+    }
+}
+
+fn convert_to_bgra(format: VideoFormat, frame_data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    match format {
+        VideoFormat::Raw(PixelFormat::Bgra) => frame_data.to_vec(),
+        VideoFormat::Raw(PixelFormat::Rgba) => frame_data.chunks_exact(4).flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]]).collect(),
+        VideoFormat::Raw(PixelFormat::Rgb) => frame_data.chunks_exact(3).flat_map(|pixel| [pixel[2], pixel[1], pixel[0], 255]).collect(),
+        VideoFormat::Y4m(chroma) => {
+            let (chroma_width, chroma_height) = chroma_plane_size(chroma, width, height);
+            let (luma_plane, chroma_planes) = frame_data.split_at(width * height);
+            let (u_plane, v_plane) = chroma_planes.split_at(chroma_width * chroma_height);
+            let mut pixels = Vec::with_capacity(width * height * 4);
+            for y in 0..height {
+                for x in 0..width {
+                    let luma = luma_plane[y * width + x];
+                    let (u, v) = if chroma == ChromaSubsampling::Mono {
+                        (128, 128)
+                    } else {
+                        let chroma_x = x * chroma_width / width;
+                        let chroma_y = y * chroma_height / height;
+                        (u_plane[chroma_y * chroma_width + chroma_x], v_plane[chroma_y * chroma_width + chroma_x])
+                    };
+                    let [r, g, b] = yuv_to_rgb(luma, u, v);
+                    pixels.extend_from_slice(&[b, g, r, 255]);
+                }
+            }
+            pixels
+        }
+    }
+}
+
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    // BT.601 limited range, what y4m streams carry unless they say otherwise
+    let (y, u, v) = (1.164 * (y as f32 - 16.0), u as f32 - 128.0, v as f32 - 128.0);
+    [
+        (y + 1.596 * v).clamp(0.0, 255.0) as u8,
+        (y - 0.813 * v - 0.391 * u).clamp(0.0, 255.0) as u8,
+        (y + 2.018 * u).clamp(0.0, 255.0) as u8
+    ]
+}
@@ -0,0 +1,161 @@
+use std::io;
+use std::time::Instant;
+use crate::effects;
+use crate::frame_source::{Frame, FrameSource};
+use crate::kernel::Kernel;
+use crate::monitor_config::Monitor;
+
+// seconds each colour is shown for by the solid colour pattern
+const SOLID_COLOUR_DURATION: f32 = 1.0;
+const SOLID_COLOURS: [[u8; 3]; 5] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255], [0, 0, 0]];
+// top, right, bottom and left bar colours
+const SIDE_BAR_COLOURS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]];
+// fraction of the screen covered by each side bar
+const SIDE_BAR_THICKNESS: f32 = 0.125;
+const CHECKERBOARD_SQUARES: usize = 8;
+// seconds for the gradient to scroll across the whole screen
+const GRADIENT_PERIOD: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestPattern {
+    SolidColours,
+    SideBars,
+    MovingGradient,
+    Checkerboard,
+    LedMarkers,
+}
+
+impl TestPattern {
+    pub(crate) const ALL: [TestPattern; 5] = [
+        TestPattern::SolidColours,
+        TestPattern::SideBars,
+        TestPattern::MovingGradient,
+        TestPattern::Checkerboard,
+        TestPattern::LedMarkers
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TestPattern::SolidColours => "solid_colours",
+            TestPattern::SideBars => "side_bars",
+            TestPattern::MovingGradient => "moving_gradient",
+            TestPattern::Checkerboard => "checkerboard",
+            TestPattern::LedMarkers => "led_markers"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TestPattern> {
+        TestPattern::ALL.iter().find(|pattern| pattern.name() == name).copied()
+    }
+}
+
+pub struct TestPatternSource {
+    pattern: TestPattern,
+    width: usize,
+    height: usize,
+    frame_rate: f32,
+    started: Instant,
+    current_frame: Option<usize>,
+    // sample points of the monitor's LEDs, only used for the marker pattern
+    pixel_locations: Vec<[usize; 2]>,
+    marker_size: [usize; 2],
+    pixels: Vec<u8>,
+}
+
+impl TestPatternSource {
+    pub fn new(pattern: TestPattern, width: usize, height: usize, frame_rate: f32, monitor: &Monitor, kernel: &Kernel) -> TestPatternSource {
+        TestPatternSource {
+            pattern,
+            width,
+            height,
+            frame_rate: frame_rate.max(0.001),
+            started: Instant::now(),
+            current_frame: None,
+            pixel_locations: monitor.get_pixel_locations(kernel, width, height),
+            marker_size: [kernel.width.max(1), kernel.height.max(1)],
+            pixels: vec![0; width * height * 4]
+        }
+    }
+
+    fn render(&mut self, time: f32) {
+        match self.pattern {
+            TestPattern::SolidColours => {
+                let colour = SOLID_COLOURS[(time / SOLID_COLOUR_DURATION) as usize % SOLID_COLOURS.len()];
+                self.fill(|_x, _y| colour);
+            },
+            TestPattern::SideBars => {
+                let (bar_width, bar_height) = ((self.width as f32 * SIDE_BAR_THICKNESS) as usize, (self.height as f32 * SIDE_BAR_THICKNESS) as usize);
+                let (width, height) = (self.width, self.height);
+                self.fill(|x, y| {
+                    if y < bar_height {
+                        SIDE_BAR_COLOURS[0]
+                    } else if x >= width - bar_width {
+                        SIDE_BAR_COLOURS[1]
+                    } else if y >= height - bar_height {
+                        SIDE_BAR_COLOURS[2]
+                    } else if x < bar_width {
+                        SIDE_BAR_COLOURS[3]
+                    } else {
+                        [0, 0, 0]
+                    }
+                });
+            },
+            TestPattern::MovingGradient => {
+                let (width, offset) = (self.width as f32, time / GRADIENT_PERIOD);
+                self.fill(|x, _y| effects::hsv_to_rgb((x as f32 / width + offset).fract(), 1.0, 1.0));
+            },
+            TestPattern::Checkerboard => {
+                let (square_width, square_height) = ((self.width / CHECKERBOARD_SQUARES).max(1), (self.height / CHECKERBOARD_SQUARES).max(1));
+                self.fill(|x, y| if (x / square_width + y / square_height) % 2 == 0 { [255, 255, 255] } else { [0, 0, 0] });
+            },
+            TestPattern::LedMarkers => {
+                // each sample point gets a hue by its position in the chain so the strip shows one rainbow from the first LED to the last
+                self.fill(|_x, _y| [0, 0, 0]);
+                let led_count = self.pixel_locations.len().max(1);
+                for (led_number, point) in self.pixel_locations.clone().iter().enumerate() {
+                    let colour = effects::hsv_to_rgb(led_number as f32 / led_count as f32 * 0.8, 1.0, 1.0);
+                    self.draw_marker(*point, colour);
+                }
+            }
+        }
+    }
+
+    fn fill<F: Fn(usize, usize) -> [u8; 3]>(&mut self, colour_at: F) {
+        for (pixel_number, pixel) in self.pixels.chunks_exact_mut(4).enumerate() {
+            let [r, g, b] = colour_at(pixel_number % self.width, pixel_number / self.width);
+            pixel.copy_from_slice(&[b, g, r, 255]);
+        }
+    }
+
+    fn draw_marker(&mut self, centre: [usize; 2], colour: [u8; 3]) {
+        // covers the kernel around the point so the LED samples the marker colour exactly
+        let left = centre[0].saturating_sub(self.marker_size[0] / 2);
+        let top = centre[1].saturating_sub(self.marker_size[1] / 2);
+        for y in top..(top + self.marker_size[1]).min(self.height) {
+            for x in left..(left + self.marker_size[0]).min(self.width) {
+                let address = (y * self.width + x) * 4;
+                self.pixels[address..address + 4].copy_from_slice(&[colour[2], colour[1], colour[0], 255]);
+            }
+        }
+    }
+}
+
+impl FrameSource for TestPatternSource {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn frame(&mut self) -> io::Result<Frame<'_>> {
+        let frame_number = (self.started.elapsed().as_secs_f32() * self.frame_rate) as usize;
+        if self.current_frame != Some(frame_number) {
+            // frames are rendered from the frame number rather than the clock so every run produces the same images
+            self.render(frame_number as f32 / self.frame_rate);
+            self.current_frame = Some(frame_number);
+        }
+        Ok(Frame::Buffer(&self.pixels))
+    }
+}
@@ -1,4 +1,8 @@
+use wide::u32x8;
+
 pub const MAX_KERNEL_SIZE: usize = 32*32;
+// pixels summed side by side in the fast path, two BGRA pixels widened to u32 fill a u32x8
+const LANE_PIXELS: usize = 2;
 
 fn get_pixel_address(x: usize, y: usize, image_width: usize, image_height: usize) -> Option<usize>{
     if y >= image_height || x >= image_width {
@@ -13,10 +17,13 @@ fn get_pixel_address(x: usize, y: usize, image_width: usize, image_height: usize
 
 #[derive(Copy, Clone)]
 pub struct Kernel{
-    pub weights: [f32; MAX_KERNEL_SIZE],
+    // only ever all 1.0 for now, uniform_weights has to be kept in step if that changes
+    weights: [f32; MAX_KERNEL_SIZE],
     pub width: usize,
     pub height: usize,
     pub coefficient: f32,
+    // every weight is 1.0, which lets whole-pixel sums be done with integers
+    uniform_weights: bool,
 }
 
 impl Default for Kernel{
@@ -25,7 +32,8 @@ impl Default for Kernel{
             weights: [1.0; MAX_KERNEL_SIZE],
             width: 1,
             height: 1,
-            coefficient: 1.0
+            coefficient: 1.0,
+            uniform_weights: true
         }
     }
 }
@@ -47,14 +55,81 @@ impl Kernel{
             weights: [1.0; MAX_KERNEL_SIZE],
             width,
             height,
-            coefficient: 1.0 / (width as f32 * height as f32)
+            coefficient: 1.0 / (width as f32 * height as f32),
+            uniform_weights: true
         }
     }
 
 
+    pub fn downscaled(&self, factor: usize) -> Kernel{
+        // keeps every factor-th weight so the kernel covers the same area of an image shrunk by factor
+        if factor <= 1 {
+            return *self;
+        }
+        let (width, height) = ((self.width / factor).max(1), (self.height / factor).max(1));
+        let mut weights = [0.0; MAX_KERNEL_SIZE];
+        for k_y in 0..height{
+            for k_x in 0..width{
+                weights[k_y * width + k_x] = self.weights[(k_y * factor) * self.width + k_x * factor];
+            }
+        }
+        Kernel{
+            weights,
+            width,
+            height,
+            // fewer weights are summed so each one counts for more
+            coefficient: self.coefficient * (self.width * self.height) as f32 / (width * height) as f32,
+            uniform_weights: self.uniform_weights
+        }
+    }
+
     pub fn kernel_pass_result(&self, image_data: &[u8], image_width: usize, image_height: usize, kernel_apply_x: usize, kernel_apply_y: usize) -> [u8; 3]{
         let kernel_left_start = self.width / 2;
         let kernel_top_start = self.height / 2;
+        let fits_inside = kernel_apply_x >= kernel_left_start && kernel_apply_y >= kernel_top_start
+            && kernel_apply_x - kernel_left_start + self.width <= image_width
+            && kernel_apply_y - kernel_top_start + self.height <= image_height;
+        if self.uniform_weights && fits_inside {
+            return self.uniform_pass_result(image_data, image_width, kernel_apply_x - kernel_left_start, kernel_apply_y - kernel_top_start);
+        }
+        self.scalar_pass_result(image_data, image_width, image_height, kernel_apply_x, kernel_apply_y)
+    }
+
+    fn uniform_pass_result(&self, image_data: &[u8], image_width: usize, left: usize, top: usize) -> [u8; 3]{
+        /*
+        Fast path for sample points away from the edges, sums whole BGRA pixels as integers one row slice at a time.
+        Each row is added LANE_PIXELS pixels at a time into a u32x8 from wide, which is portable SIMD on stable:
+        SSE or AVX on x86, NEON on ARM and plain arrays anywhere else. The lanes are folded into the channel sums at the end.
+        Sums of up to 32x32 bytes are exact in f32 as well, so this gives the same result as scalar_pass_result
+        */
+        let mut lanes = u32x8::default();
+        let mut kernel_sum = [0_u32; 4];
+        for y in top..top + self.height{
+            let row_start = (y * image_width + left) * 4;
+            // one slice per row keeps bounds checks out of the inner loop
+            let mut blocks = image_data[row_start..row_start + self.width * 4].chunks_exact(LANE_PIXELS * 4);
+            for block in &mut blocks{
+                let block: [u8; LANE_PIXELS * 4] = block.try_into().unwrap();
+                lanes += u32x8::new(block.map(u32::from));
+            }
+            for pixel in blocks.remainder().chunks_exact(4){
+                for (sum, channel) in kernel_sum.iter_mut().zip(pixel){
+                    *sum += *channel as u32;
+                }
+            }
+        }
+        for lane_pixel in lanes.to_array().chunks_exact(4){
+            for (sum, lane) in kernel_sum.iter_mut().zip(lane_pixel){
+                *sum += *lane;
+            }
+        }
+        let [b, g, r, _a] = kernel_sum;
+        [(r as f32 * self.coefficient) as u8, (g as f32 * self.coefficient) as u8, (b as f32 * self.coefficient) as u8]
+    }
+
+    fn scalar_pass_result(&self, image_data: &[u8], image_width: usize, image_height: usize, kernel_apply_x: usize, kernel_apply_y: usize) -> [u8; 3]{
+        let kernel_left_start = self.width / 2;
+        let kernel_top_start = self.height / 2;
 
         let mut kernel_sum: [f32; 3] = [0_f32; 3];
 
@@ -89,4 +164,41 @@ impl Kernel{
 
         return output;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn coordinate(size: usize) -> impl Strategy<Value = usize> {
+        // the first and last rows and columns are where the kernel hangs off the image, so they come up as often as the rest
+        prop_oneof![Just(0), Just(size - 1), 0..size]
+    }
+
+    fn sample_case() -> impl Strategy<Value = (usize, usize, Vec<u8>, (usize, usize, usize), usize, usize)> {
+        // image size, BGRA pixels, kernel width, height and downscale factor, and the point sampled
+        (1..=80_usize, 1..=60_usize, (1..=32_usize, 1..=32_usize, 1..=4_usize)).prop_flat_map(|(width, height, kernel_size)| (
+            Just(width),
+            Just(height),
+            prop::collection::vec(any::<u8>(), width * height * 4),
+            Just(kernel_size),
+            coordinate(width),
+            coordinate(height),
+        ))
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn fast_path_matches_scalar_kernel((width, height, image, (kernel_width, kernel_height, downscale), x, y) in sample_case()) {
+            let kernel = Kernel::averaging(kernel_width, kernel_height).downscaled(downscale);
+            prop_assert_eq!(
+                kernel.kernel_pass_result(&image, width, height, x, y),
+                kernel.scalar_pass_result(&image, width, height, x, y),
+                "{}x{} kernel at ({}, {}) of a {}x{} image", kernel.width, kernel.height, x, y, width, height
+            );
+        }
+    }
+}
@@ -10,24 +10,123 @@ mod monitor_config;
 mod monitor_configurer_widget;
 mod side;
 mod program_config;
+mod control_server;
+mod effects;
+mod multiplexer;
+mod network_input;
+mod audio;
+mod frame_source;
+mod capture;
+mod preview;
+mod calibration;
+mod colour_correction;
 
-use std::{path, fs};
-use iced::{Sandbox};
+use std::{path, fs, process, thread};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::mpsc::Sender;
+use iced::{Application, Sandbox};
 use clap::{arg, Parser};
 use directories::ProjectDirs;
 use crate::settings_configurer::SettingsConfigurer;
+use crate::monitor_configurer::MonitorConfigurer;
+use crate::monitor_config::MonitorConfiguration;
+use crate::program_config::ProgramConfiguration;
+use crate::frame_source::raw_video::PixelFormat;
+use crate::worker::{ControlMessage, OutputMode, DISCARD_OUTPUT};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about=None)]
 struct Args {
+    /// Open the settings window and exit once it's closed
     #[arg(short, long)]
-    configure_program: bool
+    configure_program: bool,
+    /// Open the monitor LED setup wizard and exit once it's closed
+    #[arg(long)]
+    configure_monitors: bool,
+    /// Serve the remote control page and API on this address, e.g. 0.0.0.0:8080
+    #[arg(long)]
+    http_address: Option<String>,
+    /// What drives the LEDs: capture, static_colour, static_gradient, rainbow_swirl, breathing, colour_cycle, audio_vu_meter, audio_spectrum or audio_beat_pulse
+    #[arg(long, value_parser = parse_output_mode)]
+    mode: Option<OutputMode>,
+    /// Colour used by the effects, as #rrggbb or a colour temperature like 6500K
+    #[arg(long, value_parser = parse_effect_colour)]
+    effect_colour: Option<[u8; 3]>,
+    /// Audio for the audio modes: pulse, stdin (raw 16 bit little endian PCM) or the path of a WAV file
+    #[arg(long)]
+    audio_input: Option<String>,
+    /// Sample LED colours from an image, a directory of numbered frames, a test pattern
+    /// (pattern:solid_colours, pattern:side_bars, pattern:moving_gradient, pattern:checkerboard or pattern:led_markers)
+    /// or video (raw:<path> or y4m:<path>, - for stdin) instead of the screen
+    #[arg(long)]
+    frame_source: Option<String>,
+    /// Frames per second to play a directory of frames, an animated pattern or raw video at
+    #[arg(long)]
+    frame_rate: Option<f32>,
+    /// Resolution to generate test patterns at and of raw video frames, e.g. 2560x1440
+    #[arg(long, value_parser = parse_resolution)]
+    frame_size: Option<(usize, usize)>,
+    /// Pixel layout of raw video: bgra, rgba or rgb
+    #[arg(long, value_parser = parse_pixel_format)]
+    pixel_format: Option<PixelFormat>,
+    /// Write the LED colours to this file instead of the serial port, none throws them away
+    #[arg(long)]
+    output_file: Option<String>,
+    /// Shrink captured frames by this factor before sampling, 1 samples at full resolution
+    #[arg(long)]
+    capture_downscale: Option<usize>,
+    /// Threads to sample the LEDs on
+    #[arg(long)]
+    sampling_threads: Option<usize>,
+    /// Time capturing and sampling this many frames (wall-clock) at several downscale factors and thread counts then exit
+    #[arg(long)]
+    benchmark: Option<usize>,
+    /// Run without the tray icon, stop with Ctrl+C, SIGTERM or /api/quit
+    #[arg(long)]
+    headless: bool,
+    /// Light the LEDs one at a time from the terminal to check the monitor configuration matches the wiring
+    #[arg(long)]
+    identify_leds: bool,
+    /// Show the colours sent to the LEDs in a window instead of the tray icon, closing it stops the program
+    #[arg(long)]
+    preview: bool,
+}
+
+fn parse_output_mode(name: &str) -> Result<OutputMode, String> {
+    OutputMode::from_name(name).ok_or_else(|| format!("Unknown mode {}", name))
+}
+
+fn parse_effect_colour(colour: &str) -> Result<[u8; 3], String> {
+    effects::parse_colour(colour).ok_or_else(|| format!("Invalid colour {}", colour))
+}
+
+fn parse_pixel_format(name: &str) -> Result<PixelFormat, String> {
+    PixelFormat::from_name(name).ok_or_else(|| format!("Unknown pixel format {}", name))
+}
+
+fn parse_resolution(resolution: &str) -> Result<(usize, usize), String> {
+    let (width, height) = resolution.split_once('x').ok_or_else(|| format!("Invalid resolution {}", resolution))?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(format!("Invalid resolution {}", resolution))
+    }
+}
+
+fn stop_and_exit(worker_controller: &Arc<Mutex<Sender<ControlMessage>>>) {
+    // SIGTERM and Ctrl+C fade the LEDs out the same way quitting from the tray does
+    app::stop_worker(worker_controller);
+    process::exit(0);
 }
 
 fn main() {
     let cli = Args::parse();
     if cli.configure_program {
-        SettingsConfigurer::run(SettingsConfigurer::default_window_settings(Some("assets/icon.ico"))).expect("Unable to launch settings configurer");
+        <SettingsConfigurer as Application>::run(SettingsConfigurer::default_window_settings(Some("assets/icon.ico"))).expect("Unable to launch settings configurer");
+        return;
+    }
+    if cli.configure_monitors {
+        <MonitorConfigurer as Sandbox>::run(MonitorConfigurer::window_settings(Some("assets/icon.ico"))).expect("Unable to launch monitor setup wizard");
+        return;
     }
     let config_directory = ProjectDirs::from("com", "dumfing", "monitor-ambient-lighting-rs").expect("Platform not supported");
     println!("{:?}", config_directory.config_dir());
@@ -36,8 +135,100 @@ fn main() {
         fs::create_dir_all(assets_directory).unwrap();
     }
 
-    let taskbar_app = systray::Application::new().unwrap();
-    let worker_command_channel = app::spawn_worker_thread();
-    app::setup_application(taskbar_app, worker_command_channel)
-        .wait_for_message().expect("Taskbar icon does not want to wait for messages");
+    let mut p_config = ProgramConfiguration::load_from_file(program_config::CONFIGURATION_PATH);
+    // command line choices aren't saved, and reloading the saved settings (saving from the settings window,
+    // /api/reload or "Profile" > "Saved Settings") goes back to what's in the file
+    if let Some(output_mode) = cli.mode {
+        p_config.output_mode = output_mode;
+    }
+    if let Some(colour) = cli.effect_colour {
+        p_config.effect_settings.colour = colour;
+    }
+    if let Some(audio_input) = cli.audio_input {
+        p_config.audio_settings.input = audio_input;
+    }
+    if let Some(frame_source) = cli.frame_source {
+        p_config.frame_source.input = frame_source;
+    }
+    if let Some(frame_rate) = cli.frame_rate {
+        p_config.frame_source.frame_rate = frame_rate;
+    }
+    if let Some((width, height)) = cli.frame_size {
+        p_config.frame_source.width = width;
+        p_config.frame_source.height = height;
+    }
+    if let Some(pixel_format) = cli.pixel_format {
+        p_config.frame_source.pixel_format = pixel_format;
+    }
+    if let Some(capture_downscale) = cli.capture_downscale {
+        p_config.capture_downscale = capture_downscale;
+    }
+    if let Some(sampling_threads) = cli.sampling_threads {
+        p_config.sampling_threads = sampling_threads;
+    }
+    if let Some(frames) = cli.benchmark {
+        let m_config = MonitorConfiguration::load_from_file("assets/monitor_configuration.json");
+        let mut downscales = vec![1, 2, 4, 8];
+        if !downscales.contains(&p_config.capture_downscale) {
+            downscales.push(p_config.capture_downscale);
+        }
+        let mut thread_counts = vec![1, 2, 4];
+        if !thread_counts.contains(&p_config.sampling_threads) {
+            thread_counts.push(p_config.sampling_threads);
+        }
+        if let Err(error) = capture::run_benchmark(&p_config.frame_source, &m_config, &app::capture_kernel(), &downscales, &thread_counts, frames) {
+            eprintln!("Benchmark failed: {}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(output_file) = cli.output_file {
+        p_config.output_file = Some(output_file);
+    }
+    if cli.preview && p_config.output_file.is_none() && p_config.get_open_serial_port().is_none() {
+        // the preview is still useful without the LEDs plugged in
+        println!("Unable to open serial port {}, previewing only", p_config.serial_port);
+        p_config.output_file = Some(String::from(DISCARD_OUTPUT));
+    }
+    let http_address = cli.http_address.or(p_config.http_address.clone());
+    let udp_input = p_config.udp_input_address.clone().map(|address| (address, p_config.udp_input_priority));
+    let (worker_command_channel, worker_thread) = app::spawn_worker_thread(p_config);
+    let worker_controller_copy = Arc::clone(&worker_command_channel);
+    ctrlc::set_handler(move || stop_and_exit(&worker_controller_copy)).expect("Unable to set termination handler");
+    let runs_tray = !(cli.headless || cli.identify_leds || cli.preview);
+    let (quit_sender, quit_receiver) = mpsc::channel();
+    let control_address = match http_address {
+        Some(address) => Some(address),
+        // the windows the tray launches send their changes through the control server, so the tray gets one on loopback
+        None if runs_tray => Some(String::from("127.0.0.1:0")),
+        None => None
+    }.and_then(|address| control_server::spawn_control_server(address.as_str(), Arc::clone(&worker_command_channel), quit_sender));
+    // the tray quits from its own event loop, everything else quits on /api/quit the same way as on Ctrl+C
+    let tray_quit_receiver = if runs_tray {
+        Some(quit_receiver)
+    } else {
+        let worker_controller_copy = Arc::clone(&worker_command_channel);
+        thread::spawn(move || {
+            if quit_receiver.recv().is_ok() {
+                stop_and_exit(&worker_controller_copy);
+            }
+        });
+        None
+    };
+    if let Some((address, priority)) = udp_input {
+        network_input::spawn_udp_input(address.as_str(), priority, Arc::clone(&worker_command_channel));
+    }
+    if cli.identify_leds {
+        calibration::run_identify_prompt(&worker_command_channel, &MonitorConfiguration::load_from_file("assets/monitor_configuration.json"));
+        app::stop_worker(&worker_command_channel);
+    } else if cli.preview {
+        if let Err(error) = preview::run(Arc::clone(&worker_command_channel), Some("assets/icon.ico")) {
+            eprintln!("Unable to open preview window: {}", error);
+        }
+        app::stop_worker(&worker_command_channel);
+    } else if let Some(quit_receiver) = tray_quit_receiver {
+        app::run_tray(worker_command_channel, control_address, quit_receiver);
+    }
+    // let the worker finish writing its last frame before the process exits
+    worker_thread.join().expect("Worker thread panicked");
 }
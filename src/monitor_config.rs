@@ -1,8 +1,11 @@
 extern crate json;
 
 use std::path::Path;
+use std::io;
 use std::io::Read;
+use std::fs;
 use std::fs::File;
+use json::object;
 use std::fmt;
 use std::fmt::{Formatter, Display};
 use crate::kernel::Kernel;
@@ -22,8 +25,29 @@ pub struct Bezel {
     right: f32,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureRegion {
+    // pixels from the top left of the screen
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
 pub struct LEDDirectionSequence {
-    data: Vec<SideDirection>
+    pub data: Vec<SideDirection>
+}
+
+impl LEDDirectionSequence {
+    pub fn to_order_string(&self) -> String {
+        // the same side then direction letter pairs parse_led_order reads, e.g. LTTRRBBL
+        let mut out = String::new();
+        for side_direction in &self.data {
+            out.push(side_direction.side.into());
+            out.push(side_direction.direction.into());
+        }
+        out
+    }
 }
 
 impl Default for LEDDirectionSequence{
@@ -61,65 +85,74 @@ pub struct Monitor {
     pub led_distribution: LEDCount,
     pub leds_per_inch: f32,
     pub bezel_thickness: Bezel,
+    // the part of the screen the LEDs are mapped onto, the whole screen when not set
+    pub capture_region: Option<CaptureRegion>,
 }
 
 pub struct MonitorConfiguration {
     pub monitors: Vec<Monitor>,
 }
 
-impl MonitorConfiguration {
-
-    pub fn get_pixel_locations(&self, blend_kernel: &Kernel) -> Option<Vec<[usize; 2]>>{
+impl Monitor {
+    pub fn get_pixel_locations(&self, blend_kernel: &Kernel, screen_width: usize, screen_height: usize) -> Vec<[usize; 2]>{
         let mut output = Vec::new();
-        let displays = scrap::Display::all().ok()?;
-
-        for (monitor, monitor_pixel) in self.monitors.iter().zip(displays.iter()) {
-            let bezel_length = (monitor.bezel_thickness.left + monitor.bezel_thickness.right).hypot(monitor.bezel_thickness.top + monitor.bezel_thickness.bottom);
-            let physical_diagonal = bezel_length + monitor.diagonal_size;
-            let kernel_diagonal = (blend_kernel.width as f32).hypot(blend_kernel.height as f32);
-            // subtract the kernel diagonal so that the kernel can start fully within the image, this prevents only half of the kernel being used and producing poorer results
-            // alternate solution: have coefficient be ~2 for kernel to compensate for half the kernel being black
-            let pixel_diagonal = (monitor_pixel.width() as f32).hypot(monitor_pixel.height() as f32) - kernel_diagonal;
-
-            let inch_pixel_ratio = physical_diagonal / pixel_diagonal;
-
-            let pixels_per_led = monitor.leds_per_inch * (1.0 / inch_pixel_ratio);
-
-            let _monitor_width = pixels_per_led * monitor_pixel.width() as f32;
-            let _monitor_height = pixels_per_led * monitor_pixel.height() as f32;
-
-            for side_direction in &monitor.led_order.data{
-                let (side, direction) = (side_direction.side, side_direction.direction);
-                let pixel_pos = MonitorConfiguration::get_starting_xy(side, direction, monitor_pixel, blend_kernel);
-
-                let num_leds = match side {
-                    Side::LEFT => monitor.led_distribution.left,
-                    Side::RIGHT => monitor.led_distribution.right,
-                    Side::TOP => monitor.led_distribution.top,
-                    Side::BOTTOM => monitor.led_distribution.bottom,
-                    Side::ERROR => 0
-                };
+        // sample points are laid out around the edges of the capture region then moved to where it sits on the screen
+        let region = self.get_capture_region(screen_width, screen_height);
+        let (screen_width, screen_height) = (region.width, region.height);
+        let bezel_length = (self.bezel_thickness.left + self.bezel_thickness.right).hypot(self.bezel_thickness.top + self.bezel_thickness.bottom);
+        let physical_diagonal = bezel_length + self.diagonal_size;
+        let kernel_diagonal = (blend_kernel.width as f32).hypot(blend_kernel.height as f32);
+        // subtract the kernel diagonal so that the kernel can start fully within the image, this prevents only half of the kernel being used and producing poorer results
+        // alternate solution: have coefficient be ~2 for kernel to compensate for half the kernel being black
+        let pixel_diagonal = (screen_width as f32).hypot(screen_height as f32) - kernel_diagonal;
+
+        let inch_pixel_ratio = physical_diagonal / pixel_diagonal;
+
+        let pixels_per_led = self.leds_per_inch * (1.0 / inch_pixel_ratio);
+
+        for side_direction in &self.led_order.data{
+            let (side, direction) = (side_direction.side, side_direction.direction);
+            let pixel_pos = Monitor::get_starting_xy(side, direction, screen_width, screen_height, blend_kernel);
+
+            let num_leds = self.led_distribution.get(side);
+
+            let step_amount = match direction {
+                Side::LEFT => [-pixels_per_led, 0.0],
+                Side::RIGHT => [pixels_per_led, 0.0],
+                Side::TOP => [0.0, -pixels_per_led],
+                Side::BOTTOM => [0.0, pixels_per_led],
+                Side::ERROR => [0.0, 0.0]
+            };
 
-                let step_amount = match direction {
-                    Side::LEFT => [-pixels_per_led, 0.0],
-                    Side::RIGHT => [pixels_per_led, 0.0],
-                    Side::TOP => [0.0, -pixels_per_led],
-                    Side::BOTTOM => [0.0, pixels_per_led],
-                    Side::ERROR => [0.0, 0.0]
-                };
+            for led_number in 0..num_leds{
+                output.push([region.x + (pixel_pos[0] + (step_amount[0] * led_number as f32)).round() as usize, region.y + (pixel_pos[1] + (step_amount[1] * led_number as f32)).round() as usize]);
+            };
+        }
+        output
+    }
 
-                for led_number in 0..num_leds{
-                    output.push([(pixel_pos[0] + (step_amount[0] * led_number as f32)).round() as usize, (pixel_pos[1] + (step_amount[1] * led_number as f32)).round() as usize]);
-                };
-            }
-        };
-        Some(output)
+    pub fn get_capture_region(&self, screen_width: usize, screen_height: usize) -> CaptureRegion {
+        // regions hanging off the screen are cut down to fit
+        let region = self.capture_region.unwrap_or(CaptureRegion {
+            x: 0,
+            y: 0,
+            width: screen_width,
+            height: screen_height
+        });
+        let x = region.x.min(screen_width.saturating_sub(1));
+        let y = region.y.min(screen_height.saturating_sub(1));
+        CaptureRegion {
+            x,
+            y,
+            width: region.width.clamp(1, (screen_width - x).max(1)),
+            height: region.height.clamp(1, (screen_height - y).max(1))
+        }
     }
 
-    fn get_starting_xy(side: Side, direction: Side, screen: &scrap::Display, kernel_info : &Kernel) -> [f32; 2]{
+    fn get_starting_xy(side: Side, direction: Side, screen_width: usize, screen_height: usize, kernel_info : &Kernel) -> [f32; 2]{
         let mut output: [f32; 2] = [0.0, 0.0];
         let (half_kernel_width, half_kernel_height) = (kernel_info.width as f32/2.0, kernel_info.height as f32/2.0);
-        let (screen_width, screen_height) = (screen.width() as f32, screen.height() as f32);
+        let (screen_width, screen_height) = (screen_width as f32, screen_height as f32);
         match side {
             Side::LEFT => {
                 output[0] = half_kernel_width;
@@ -153,6 +186,38 @@ impl MonitorConfiguration {
         }
         output
     }
+}
+
+impl MonitorConfiguration {
+
+    pub fn get_pixel_locations(&self, blend_kernel: &Kernel, screen_sizes: &[(usize, usize)]) -> Vec<[usize; 2]>{
+        /*
+        screen_sizes holds the width and height of the image each monitor's LEDs are sampled from, in monitor order
+        */
+        let mut output = Vec::new();
+        for (monitor, (screen_width, screen_height)) in self.monitors.iter().zip(screen_sizes.iter()) {
+            output.extend(monitor.get_pixel_locations(blend_kernel, *screen_width, *screen_height));
+        };
+        output
+    }
+
+    pub fn get_led_sides(&self) -> Vec<(Side, f32)> {
+        /*
+        The side each LED sits on and how far along that side it is in chain order (0 to 1),
+        in the same order as get_pixel_locations
+        */
+        let mut output = Vec::new();
+        for monitor in &self.monitors {
+            for side_direction in &monitor.led_order.data {
+                let num_leds = monitor.led_distribution.get(side_direction.side);
+                for led_number in 0..num_leds {
+                    let progress = if num_leds > 1 { led_number as f32 / (num_leds - 1) as f32 } else { 0.0 };
+                    output.push((side_direction.side, progress));
+                }
+            }
+        }
+        output
+    }
 
     pub fn load_from_file(path: &str) -> MonitorConfiguration {
         MonitorConfiguration::try_load(path).unwrap_or_default()
@@ -186,6 +251,7 @@ impl MonitorConfiguration {
                     right: monitor_data["bezel_thickness"]["right"].as_f32()?,
                     bottom: monitor_data["bezel_thickness"]["bottom"].as_f32()?,
                 },
+                capture_region: MonitorConfiguration::parse_capture_region(&monitor_data["capture_region"]),
             };
             all_monitors.push(monitor_instance);
         };
@@ -195,6 +261,53 @@ impl MonitorConfiguration {
         Some(output)
     }
 
+    pub fn save_to_file(&self, path: &str) -> io::Result<()> {
+        let mut monitor_data = Vec::new();
+        for monitor in &self.monitors {
+            let mut monitor_json = object!{
+                "monitor": monitor.monitor_number,
+                "led_order": monitor.led_order.to_order_string(),
+                "diagonal_size": monitor.diagonal_size,
+                "led_count": object!{
+                    "right": monitor.led_distribution.right,
+                    "top": monitor.led_distribution.top,
+                    "left": monitor.led_distribution.left,
+                    "bottom": monitor.led_distribution.bottom
+                },
+                "leds_per_inch": monitor.leds_per_inch,
+                "bezel_thickness": object!{
+                    "top": monitor.bezel_thickness.top,
+                    "bottom": monitor.bezel_thickness.bottom,
+                    "left": monitor.bezel_thickness.left,
+                    "right": monitor.bezel_thickness.right
+                }
+            };
+            if let Some(region) = monitor.capture_region {
+                monitor_json["capture_region"] = object!{
+                    "x": region.x,
+                    "y": region.y,
+                    "width": region.width,
+                    "height": region.height
+                };
+            }
+            monitor_data.push(monitor_json);
+        }
+        let json_out = object!{
+            "monitor_configuration": json::JsonValue::Array(monitor_data)
+        };
+        fs::write(Path::new(path), json_out.pretty(4))
+    }
+
+    fn parse_capture_region(region_data: &json::JsonValue) -> Option<CaptureRegion> {
+        // optional, older configurations capture the whole screen
+        Some(CaptureRegion {
+            x: region_data["x"].as_usize()?,
+            y: region_data["y"].as_usize()?,
+            width: region_data["width"].as_usize()?,
+            height: region_data["height"].as_usize()?,
+        })
+    }
+
     fn parse_led_order(to_parse: &str) -> LEDDirectionSequence {
         let mut out = Vec::new();
         let mut parse_chars = to_parse.chars();
@@ -232,12 +345,57 @@ impl Default for MonitorConfiguration{
                     led_distribution: LEDCount::default(),
                     leds_per_inch: 0.0,
                     bezel_thickness: Bezel::default(),
+                    capture_region: None,
                 }
             ]
         }
     }
 }
 
+impl LEDCount {
+    pub fn get(&self, side: Side) -> usize {
+        match side {
+            Side::LEFT => self.left,
+            Side::RIGHT => self.right,
+            Side::TOP => self.top,
+            Side::BOTTOM => self.bottom,
+            Side::ERROR => 0
+        }
+    }
+
+    pub fn set(&mut self, side: Side, count: usize) {
+        match side {
+            Side::LEFT => self.left = count,
+            Side::RIGHT => self.right = count,
+            Side::TOP => self.top = count,
+            Side::BOTTOM => self.bottom = count,
+            Side::ERROR => {}
+        }
+    }
+}
+
+impl Bezel {
+    pub fn get(&self, side: Side) -> f32 {
+        match side {
+            Side::LEFT => self.left,
+            Side::RIGHT => self.right,
+            Side::TOP => self.top,
+            Side::BOTTOM => self.bottom,
+            Side::ERROR => 0.0
+        }
+    }
+
+    pub fn set(&mut self, side: Side, thickness: f32) {
+        match side {
+            Side::LEFT => self.left = thickness,
+            Side::RIGHT => self.right = thickness,
+            Side::TOP => self.top = thickness,
+            Side::BOTTOM => self.bottom = thickness,
+            Side::ERROR => {}
+        }
+    }
+}
+
 impl Default for LEDCount{
     fn default() -> Self {
         LEDCount{
@@ -277,13 +435,14 @@ impl fmt::Display for MonitorConfiguration {
 
 impl fmt::Display for Monitor {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "Display{{Number: {}, led_order: {}, diagonal_size: {}, led_count: {}, leds_per_inch: {}, bezel_thickness: {}}}",
+        write!(f, "Display{{Number: {}, led_order: {}, diagonal_size: {}, led_count: {}, leds_per_inch: {}, bezel_thickness: {}, capture_region: {:?}}}",
                self.monitor_number,
                self.led_order,
                self.diagonal_size,
                self.led_distribution,
                self.leds_per_inch,
-               self.bezel_thickness
+               self.bezel_thickness,
+               self.capture_region
         )
     }
 }
@@ -1,24 +1,255 @@
 extern crate iced;
 
-use self::iced::{Sandbox, Element};
+use self::iced::{Sandbox, Element, Container, Length, Row, Column, Alignment, Text, TextInput, text_input, PickList, pick_list, Button, button, Checkbox, Canvas};
+use iced::settings::Settings;
+use iced::alignment::Horizontal;
+use std::fmt::{Display, Formatter};
+use std::process::exit;
+use crate::app;
+use crate::calibration::{self, IdentifyPattern};
+use crate::monitor_config::{Bezel, CaptureRegion, LEDCount, LEDDirectionSequence, Monitor, MonitorConfiguration};
+use crate::monitor_configurer_widget;
+use crate::settings_configurer::SettingsConfigurer;
+use crate::side::{Side, SideDirection};
 
+const MONITOR_CONFIGURATION_PATH: &str = "assets/monitor_configuration.json";
 
-struct MonitorConfigurer {
-    sides_present: [bool; 4]
+// size the layout is drawn at for monitors that aren't connected
+const DEFAULT_SCREEN_SIZE: (usize, usize) = (1920, 1080);
+// the order of sides_present, flipped_sides, led_counts and bezel_thickness
+pub(crate) const SIDES: [Side; 4] = [Side::TOP, Side::LEFT, Side::BOTTOM, Side::RIGHT];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
 }
 
-#[derive(Debug, Clone)]
-enum Message{
+impl Corner {
+    // clockwise from the top left
+    pub(crate) const ALL: [Corner; 4] = [Corner::TopLeft, Corner::TopRight, Corner::BottomRight, Corner::BottomLeft];
 }
 
-impl Default for MonitorConfigurer {
+impl Default for Corner {
+    fn default() -> Self {
+        Corner::TopLeft
+    }
+}
+
+impl Display for Corner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Corner::TopLeft => "top left",
+                Corner::TopRight => "top right",
+                Corner::BottomRight => "bottom right",
+                Corner::BottomLeft => "bottom left",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Winding {
+    Clockwise,
+    Anticlockwise,
+}
+
+impl Winding {
+    pub(crate) const ALL: [Winding; 2] = [Winding::Clockwise, Winding::Anticlockwise];
+}
+
+impl Default for Winding {
     fn default() -> Self {
-        MonitorConfigurer{
-            sides_present: [false; 4]
+        Winding::Clockwise
+    }
+}
+
+impl Display for Winding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Winding::Clockwise => "clockwise",
+                Winding::Anticlockwise => "anticlockwise",
+            }
+        )
+    }
+}
+
+pub fn led_order(start_corner: Corner, winding: Winding, sides_present: [bool; 4], flipped_sides: [bool; 4]) -> Vec<SideDirection> {
+    /*
+    The strips in the order the chain runs through them when it starts at start_corner and goes around the screen
+    in the winding direction, as seen from the front. Sides without a strip are skipped and
+    flipped sides are counted against the winding
+    */
+    let (strips, start) = match winding {
+        Winding::Clockwise => (
+            [(Side::TOP, Side::RIGHT), (Side::RIGHT, Side::BOTTOM), (Side::BOTTOM, Side::LEFT), (Side::LEFT, Side::TOP)],
+            Corner::ALL.iter().position(|corner| *corner == start_corner).unwrap_or(0)
+        ),
+        Winding::Anticlockwise => (
+            [(Side::LEFT, Side::BOTTOM), (Side::BOTTOM, Side::RIGHT), (Side::RIGHT, Side::TOP), (Side::TOP, Side::LEFT)],
+            (4 - Corner::ALL.iter().position(|corner| *corner == start_corner).unwrap_or(0)) % 4
+        ),
+    };
+    (0..4).map(|i| strips[(start + i) % 4])
+        .filter(|(side, _direction)| side_index(*side).is_some_and(|index| sides_present[index]))
+        .map(|(side, direction)| SideDirection {
+            side,
+            direction: if side_index(side).is_some_and(|index| flipped_sides[index]) { direction.opposite() } else { direction }
+        })
+        .collect()
+}
+
+pub fn describe_led_order(strips: &[SideDirection]) -> (Corner, Winding, [bool; 4]) {
+    /*
+    The corner, winding and flipped sides that produce strips, preferring the fewest flips.
+    Orders that don't go around the screen fall back to the default
+    */
+    let sides_present = SIDES.map(|side| strips.iter().any(|strip| strip.side == side));
+    let mut best: Option<(Corner, Winding, [bool; 4])> = None;
+    for winding in Winding::ALL {
+        for corner in Corner::ALL {
+            let unflipped = led_order(corner, winding, sides_present, [false; 4]);
+            if unflipped.len() != strips.len() || unflipped.iter().zip(strips).any(|(a, b)| a.side != b.side) {
+                continue;
+            }
+            let mut flipped_sides = [false; 4];
+            for (a, b) in unflipped.iter().zip(strips) {
+                if let Some(index) = side_index(a.side) {
+                    flipped_sides[index] = a.direction != b.direction;
+                }
+            }
+            let flips = flipped_sides.iter().filter(|flipped| **flipped).count();
+            if best.is_none_or(|(_, _, best_flipped)| flips < best_flipped.iter().filter(|flipped| **flipped).count()) {
+                best = Some((corner, winding, flipped_sides));
+            }
         }
     }
+    best.unwrap_or((Corner::default(), Winding::default(), [false; 4]))
+}
+
+pub(crate) fn side_index(side: Side) -> Option<usize> {
+    SIDES.iter().position(|s| *s == side)
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayOption {
+    number: usize,
+    // 0 for configured monitors that aren't connected
+    width: usize,
+    height: usize,
+}
+
+impl Display for DisplayOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.width == 0 {
+            write!(f, "Display {} (not connected)", self.number)
+        } else {
+            write!(f, "Display {} ({}x{})", self.number, self.width, self.height)
+        }
+    }
+}
+
+pub struct MonitorConfigurer {
+    display_options_state: pick_list::State<DisplayOption>,
+    start_corner_state: pick_list::State<Corner>,
+    winding_state: pick_list::State<Winding>,
+    led_count_states: [text_input::State; 4],
+    diagonal_size_state: text_input::State,
+    leds_per_inch_state: text_input::State,
+    bezel_states: [text_input::State; 4],
+    save_button_state: button::State,
+    ok_button_state: button::State,
+    identify_previous_state: button::State,
+    identify_next_state: button::State,
+    identify_sides_state: button::State,
+    identify_stop_state: button::State,
+    layout_state: monitor_configurer_widget::State,
+
+    display_options: Vec<DisplayOption>,
+    values: FieldValues,
+    // every monitor in the file, the one being edited is written back into it when switching display or saving
+    configuration: MonitorConfiguration,
+    unsaved_changes: bool,
+    save_error: Option<String>,
+    // what's lit on the strip, LEDs are counted from the first one on the monitor being edited
+    identifying: Option<IdentifyPattern>,
+    identify_led: usize,
+    identify_description: String,
+}
+
+#[derive(Clone, PartialEq)]
+struct FieldValues {
+    monitor_number: usize,
+    sides_present: [bool; 4],
+    led_counts: [String; 4],
+    start_corner: Corner,
+    winding: Winding,
+    flipped_sides: [bool; 4],
+    diagonal_size: String,
+    leds_per_inch: String,
+    bezel_thickness: [String; 4],
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    DisplaySelected(DisplayOption),
+    SideToggled(usize, bool),
+    LEDCountChanged(usize, String),
+    StartCornerSelected(Corner),
+    WindingSelected(Winding),
+    SideFlipped(usize),
+    DiagonalSizeChanged(String),
+    LEDsPerInchChanged(String),
+    BezelChanged(usize, String),
+    IdentifyPrevious,
+    IdentifyNext,
+    IdentifySides,
+    IdentifyStop,
+    SaveFile,
+    SaveAndExit,
+}
+
+impl Default for MonitorConfigurer {
+    fn default() -> Self {
+        let configuration = MonitorConfiguration::load_from_file(MONITOR_CONFIGURATION_PATH);
+        let display_options = MonitorConfigurer::get_display_options(&configuration);
+        let monitor_number = configuration.monitors.first().map_or(1, |monitor| monitor.monitor_number);
+        let mut configurer = MonitorConfigurer {
+            display_options_state: Default::default(),
+            start_corner_state: Default::default(),
+            winding_state: Default::default(),
+            led_count_states: Default::default(),
+            diagonal_size_state: Default::default(),
+            leds_per_inch_state: Default::default(),
+            bezel_states: Default::default(),
+            save_button_state: Default::default(),
+            ok_button_state: Default::default(),
+            identify_previous_state: Default::default(),
+            identify_next_state: Default::default(),
+            identify_sides_state: Default::default(),
+            identify_stop_state: Default::default(),
+            layout_state: Default::default(),
+            display_options,
+            values: FieldValues::for_monitor(&configuration, monitor_number),
+            configuration,
+            unsaved_changes: false,
+            save_error: None,
+            identifying: None,
+            identify_led: 0,
+            identify_description: String::new(),
+        };
+        configurer.refresh_layout();
+        configurer
+    }
+}
 
 impl Sandbox for MonitorConfigurer {
     type Message = Message;
@@ -28,15 +259,450 @@ impl Sandbox for MonitorConfigurer {
     }
 
     fn title(&self) -> String {
-        "Monitor LED Setup Wizard".into()
+        if self.unsaved_changes {
+            "Monitor LED Setup Wizard | Unsaved Changes".into()
+        } else {
+            "Monitor LED Setup Wizard".into()
+        }
     }
 
-    fn update(&mut self, _: Self::Message) {
-        unimplemented!()
+    fn update(&mut self, message: Self::Message) {
+        match message {
+            Message::DisplaySelected(display) => {
+                // edits to the previous display are kept for when the file is saved
+                if self.store_values().is_ok() {
+                    self.values = FieldValues::for_monitor(&self.configuration, display.number);
+                    self.refresh_layout();
+                    self.identify_led = 0;
+                    if self.identifying.is_some() {
+                        self.identify(self.identifying);
+                    }
+                }
+                return;
+            }
+            Message::SideToggled(index, present) => self.values.sides_present[index] = present,
+            Message::LEDCountChanged(index, count) => self.values.led_counts[index] = count,
+            // flips are relative to the start and winding, so they're dropped to keep the start where it was put
+            Message::StartCornerSelected(corner) => {
+                self.values.start_corner = corner;
+                self.values.flipped_sides = [false; 4];
+            }
+            Message::WindingSelected(winding) => {
+                self.values.winding = winding;
+                self.values.flipped_sides = [false; 4];
+            }
+            Message::SideFlipped(index) => self.values.flipped_sides[index] = !self.values.flipped_sides[index],
+            Message::DiagonalSizeChanged(size) => self.values.diagonal_size = size,
+            Message::LEDsPerInchChanged(density) => self.values.leds_per_inch = density,
+            Message::BezelChanged(index, thickness) => self.values.bezel_thickness[index] = thickness,
+            Message::SaveFile => {
+                self.save_config();
+                return;
+            }
+            Message::SaveAndExit => {
+                if self.save_config() {
+                    if self.identifying.is_some() {
+                        self.identify(None);
+                    }
+                    exit(0);
+                }
+                return;
+            }
+            Message::IdentifyPrevious | Message::IdentifyNext => {
+                // coming back from the sides carries on from the LED shown before them
+                let led_count = self.values.led_count().max(1);
+                if matches!(self.identifying, Some(IdentifyPattern::Led(_))) {
+                    self.identify_led = match message {
+                        Message::IdentifyNext => (self.identify_led + 1) % led_count,
+                        _ => (self.identify_led + led_count - 1) % led_count,
+                    };
+                }
+                self.identify(Some(IdentifyPattern::Led(self.identify_led)));
+                return;
+            }
+            Message::IdentifySides => {
+                self.identify(Some(IdentifyPattern::Sides));
+                return;
+            }
+            Message::IdentifyStop => {
+                self.identify(None);
+                return;
+            }
+        }
+        self.unsaved_changes = true;
+        self.save_error = None;
+        self.refresh_layout();
+        // what's lit follows the changes so it always matches the canvas
+        self.identify_led = self.identify_led.min(self.values.led_count().saturating_sub(1));
+        if self.identifying.is_some() {
+            self.identify(self.identifying);
+        }
     }
 
     fn view(&mut self) -> Element<'_, Self::Message> {
-        unimplemented!()
+        let values = self.values.clone();
+        let validation = values.validate();
+
+        let selected_display = self.display_options.iter().find(|display| display.number == values.monitor_number).cloned();
+        let display_picker = PickList::new(
+            &mut self.display_options_state,
+            self.display_options.clone(),
+            selected_display,
+            Message::DisplaySelected,
+        )
+            .text_size(20);
+
+        let mut sides_row = Row::new()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(Text::new("Strips on the"));
+        for (index, side) in SIDES.iter().enumerate() {
+            sides_row = sides_row.push(Checkbox::new(values.sides_present[index], side.to_string(), move |present| Message::SideToggled(index, present)));
+        }
+
+        let mut led_count_row = Row::new()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(Text::new("LEDs"));
+        for (index, state) in self.led_count_states.iter_mut().enumerate() {
+            if !values.sides_present[index] {
+                continue;
+            }
+            led_count_row = led_count_row
+                .push(Text::new(SIDES[index].to_string()).size(20))
+                .push(TextInput::new(state, "0", values.led_counts[index].as_str(), move |count| Message::LEDCountChanged(index, count))
+                    .width(Length::Units(60)));
+        }
+
+        let start_corner_picker = PickList::new(
+            &mut self.start_corner_state,
+            &Corner::ALL[..],
+            Some(values.start_corner),
+            Message::StartCornerSelected,
+        )
+            .text_size(20);
+
+        let winding_picker = PickList::new(
+            &mut self.winding_state,
+            &Winding::ALL[..],
+            Some(values.winding),
+            Message::WindingSelected,
+        )
+            .text_size(20);
+
+        let size_row = Row::new()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(Text::new("Screen is"))
+            .push(TextInput::new(&mut self.diagonal_size_state, "24.0", values.diagonal_size.as_str(), Message::DiagonalSizeChanged)
+                .width(Length::Units(80)))
+            .push(Text::new("inches with"))
+            .push(TextInput::new(&mut self.leds_per_inch_state, "1.3", values.leds_per_inch.as_str(), Message::LEDsPerInchChanged)
+                .width(Length::Units(80)))
+            .push(Text::new("LEDs per inch."));
+
+        let mut bezel_row = Row::new()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(Text::new("Bezel inches"));
+        for (index, state) in self.bezel_states.iter_mut().enumerate() {
+            bezel_row = bezel_row
+                .push(Text::new(SIDES[index].to_string()).size(20))
+                .push(TextInput::new(state, "0.0", values.bezel_thickness[index].as_str(), move |thickness| Message::BezelChanged(index, thickness))
+                    .width(Length::Units(70)));
+        }
+
+        let order_summary = led_order(values.start_corner, values.winding, values.sides_present, values.flipped_sides).iter()
+            .map(|strip| format!("{} towards the {}", strip.side, strip.direction.to_string().to_lowercase()))
+            .collect::<Vec<String>>()
+            .join(", then ");
+
+        let mut save_button = Button::new(
+            &mut self.save_button_state,
+            Text::new("Save")
+        );
+        let mut ok_button = Button::new(
+            &mut self.ok_button_state,
+            Text::new("OK")
+        );
+        // saving is only possible once every field makes sense
+        if validation.is_ok() {
+            save_button = save_button.on_press(Message::SaveFile);
+            ok_button = ok_button.on_press(Message::SaveAndExit);
+        }
+
+        let mut identify_previous_button = Button::new(&mut self.identify_previous_state, Text::new("Previous"));
+        let mut identify_next_button = Button::new(&mut self.identify_next_state, Text::new("Next"));
+        let mut identify_sides_button = Button::new(&mut self.identify_sides_state, Text::new("Sides"));
+        let mut identify_stop_button = Button::new(&mut self.identify_stop_state, Text::new("Stop"));
+        if validation.is_ok() {
+            identify_previous_button = identify_previous_button.on_press(Message::IdentifyPrevious);
+            identify_next_button = identify_next_button.on_press(Message::IdentifyNext);
+            identify_sides_button = identify_sides_button.on_press(Message::IdentifySides);
+        }
+        if self.identifying.is_some() {
+            identify_stop_button = identify_stop_button.on_press(Message::IdentifyStop);
+        }
+        let identify_row = Row::new()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(Text::new("Identify LEDs"))
+            .push(identify_previous_button)
+            .push(identify_next_button)
+            .push(identify_sides_button)
+            .push(identify_stop_button)
+            .push(Text::new(self.identify_description.as_str()).size(18));
+
+        let layout_canvas = Canvas::new(&mut self.layout_state)
+            .width(Length::Fill)
+            .height(Length::Units(320));
+
+        let mut wizard_column = Column::new()
+            .push(Row::new()
+                .align_items(Alignment::Center)
+                .spacing(10)
+                .push(Text::new("Set up"))
+                .push(display_picker))
+            .push(layout_canvas)
+            .push(identify_row)
+            .push(sides_row)
+            .push(led_count_row)
+            .push(Row::new()
+                .align_items(Alignment::Center)
+                .spacing(10)
+                .push(Text::new("The strip starts at the"))
+                .push(start_corner_picker)
+                .push(Text::new("corner and runs"))
+                .push(winding_picker))
+            .push(Text::new(format!("Chain: {}", order_summary)).size(18))
+            .push(size_row)
+            .push(bezel_row)
+            .spacing(20);
+
+        if let Err(error) = validation.and(self.save_error.clone().map_or(Ok(()), Err)) {
+            wizard_column = wizard_column.push(Text::new(error).size(18).color([0.8, 0.0, 0.0]));
+        }
+
+        wizard_column = wizard_column.push(Row::new()
+            .push(save_button)
+            .push(ok_button)
+            .spacing(15)
+            .align_items(Alignment::End));
+
+        Container::new(wizard_column)
+            .center_x()
+            .center_y()
+            .align_x(Horizontal::Center)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+}
+
+impl MonitorConfigurer {
+    pub fn window_settings(path_in_opt: Option<&str>) -> Settings<()> {
+        let mut out = SettingsConfigurer::default_window_settings(path_in_opt);
+        out.window.size = (820, 920);
+        out
+    }
+
+    fn get_display_options(configuration: &MonitorConfiguration) -> Vec<DisplayOption> {
+        // monitors are numbered from 1 in the order scrap lists the displays
+        let mut options: Vec<DisplayOption> = scrap::Display::all().unwrap_or_default().iter().enumerate()
+            .map(|(index, display)| DisplayOption {
+                number: index + 1,
+                width: display.width(),
+                height: display.height(),
+            })
+            .collect();
+        for monitor in &configuration.monitors {
+            if !options.iter().any(|option| option.number == monitor.monitor_number) {
+                options.push(DisplayOption {
+                    number: monitor.monitor_number,
+                    width: 0,
+                    height: 0,
+                });
+            }
+        }
+        options.sort_by_key(|option| option.number);
+        options
+    }
+
+    fn store_values(&mut self) -> Result<(), String> {
+        /*
+        Writes the fields into the monitor being edited, adding it to the configuration if it wasn't there before.
+        Anything the wizard doesn't edit, like the capture region, is kept
+        */
+        self.values.validate()?;
+        match self.configuration.monitors.iter_mut().find(|monitor| monitor.monitor_number == self.values.monitor_number) {
+            Some(monitor) => *monitor = self.values.build_monitor(monitor.capture_region),
+            None => self.configuration.monitors.push(self.values.build_monitor(None)),
+        }
+        Ok(())
     }
 
-}
\ No newline at end of file
+    fn identify(&mut self, pattern: Option<IdentifyPattern>) {
+        /*
+        Sends the colours to light to the tray that launched the wizard, which passes them on to the worker.
+        The whole configuration is lit so LED numbers match the chain, fields that don't make sense yet leave the strip as it is
+        */
+        self.identifying = pattern;
+        let colours = match pattern {
+            Some(pattern) => {
+                if self.store_values().is_err() {
+                    return;
+                }
+                let first_led = self.first_chain_led();
+                let chain_pattern = match pattern {
+                    IdentifyPattern::Led(led) => IdentifyPattern::Led(first_led + led),
+                    IdentifyPattern::Sides => IdentifyPattern::Sides,
+                };
+                self.identify_description = match chain_pattern {
+                    IdentifyPattern::Led(led) => calibration::describe_led(&self.configuration, led),
+                    IdentifyPattern::Sides => String::from("Top red, right green, bottom blue, left yellow"),
+                };
+                Some(calibration::identify_colours(&self.configuration, chain_pattern))
+            }
+            None => {
+                self.identify_description.clear();
+                None
+            }
+        };
+        self.layout_state.set_highlighted_led(match pattern {
+            Some(IdentifyPattern::Led(led)) => Some(led),
+            _ => None,
+        });
+        calibration::send_identify_to_tray(colours.as_deref());
+    }
+
+    fn first_chain_led(&self) -> usize {
+        // LEDs on the monitors before this one in the chain, numbers on the canvas and in descriptions carry on from them
+        self.configuration.monitors.iter()
+            .take_while(|monitor| monitor.monitor_number != self.values.monitor_number)
+            .map(|monitor| monitor.led_order.data.iter().map(|strip| monitor.led_distribution.get(strip.side)).sum::<usize>())
+            .sum()
+    }
+
+    fn refresh_layout(&mut self) {
+        // the canvas shows the LEDs where the worker would sample them, nothing is placed until the fields make sense
+        let screen_size = self.display_options.iter()
+            .find(|display| display.number == self.values.monitor_number && display.width > 0)
+            .map_or(DEFAULT_SCREEN_SIZE, |display| (display.width, display.height));
+        let strips = led_order(self.values.start_corner, self.values.winding, self.values.sides_present, self.values.flipped_sides);
+        let (pixel_locations, led_sides) = if self.values.validate().is_ok() {
+            let capture_region = self.configuration.monitors.iter()
+                .find(|monitor| monitor.monitor_number == self.values.monitor_number)
+                .and_then(|monitor| monitor.capture_region);
+            let monitor = self.values.build_monitor(capture_region);
+            let led_sides = strips.iter()
+                .flat_map(|strip| vec![strip.side; monitor.led_distribution.get(strip.side)])
+                .collect();
+            (monitor.get_pixel_locations(&app::capture_kernel(), screen_size.0, screen_size.1), led_sides)
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let first_led = self.first_chain_led();
+        self.layout_state.set_layout(strips, pixel_locations, led_sides, screen_size, first_led);
+    }
+
+    fn save_config(&mut self) -> bool {
+        let result = self.store_values()
+            .and_then(|_| self.configuration.save_to_file(MONITOR_CONFIGURATION_PATH)
+                .map_err(|error| format!("Unable to save {}: {}", MONITOR_CONFIGURATION_PATH, error)));
+        match result {
+            Ok(()) => {
+                self.unsaved_changes = false;
+                self.save_error = None;
+                true
+            }
+            Err(error) => {
+                self.save_error = Some(error);
+                false
+            }
+        }
+    }
+}
+
+impl FieldValues {
+    fn for_monitor(configuration: &MonitorConfiguration, monitor_number: usize) -> FieldValues {
+        match configuration.monitors.iter().find(|monitor| monitor.monitor_number == monitor_number) {
+            Some(monitor) if !monitor.led_order.data.is_empty() => {
+                let (start_corner, winding, flipped_sides) = describe_led_order(&monitor.led_order.data);
+                FieldValues {
+                    monitor_number,
+                    sides_present: SIDES.map(|side| monitor.led_order.data.iter().any(|strip| strip.side == side)),
+                    led_counts: SIDES.map(|side| monitor.led_distribution.get(side).to_string()),
+                    start_corner,
+                    winding,
+                    flipped_sides,
+                    diagonal_size: monitor.diagonal_size.to_string(),
+                    leds_per_inch: monitor.leds_per_inch.to_string(),
+                    bezel_thickness: SIDES.map(|side| monitor.bezel_thickness.get(side).to_string()),
+                }
+            }
+            // monitors that haven't been set up yet start with a strip on every side
+            _ => FieldValues {
+                monitor_number,
+                sides_present: [true; 4],
+                led_counts: Default::default(),
+                start_corner: Corner::default(),
+                winding: Winding::default(),
+                flipped_sides: [false; 4],
+                diagonal_size: String::new(),
+                leds_per_inch: String::new(),
+                bezel_thickness: SIDES.map(|_| String::from("0.0")),
+            }
+        }
+    }
+
+    fn build_monitor(&self, capture_region: Option<CaptureRegion>) -> Monitor {
+        // only meaningful once validate passes, fields that don't parse come out as 0
+        let mut led_distribution = LEDCount::default();
+        let mut bezel_thickness = Bezel::default();
+        for (index, side) in SIDES.iter().enumerate() {
+            // sides without a strip are saved with no LEDs
+            let count = if self.sides_present[index] { self.led_counts[index].trim().parse().unwrap_or(0) } else { 0 };
+            led_distribution.set(*side, count);
+            bezel_thickness.set(*side, self.bezel_thickness[index].trim().parse().unwrap_or(0.0));
+        }
+        Monitor {
+            monitor_number: self.monitor_number,
+            led_order: LEDDirectionSequence {
+                data: led_order(self.start_corner, self.winding, self.sides_present, self.flipped_sides)
+            },
+            diagonal_size: self.diagonal_size.trim().parse().unwrap_or(0.0),
+            led_distribution,
+            leds_per_inch: self.leds_per_inch.trim().parse().unwrap_or(0.0),
+            bezel_thickness,
+            capture_region,
+        }
+    }
+
+    fn led_count(&self) -> usize {
+        (0..SIDES.len())
+            .filter(|index| self.sides_present[*index])
+            .map(|index| self.led_counts[index].trim().parse::<usize>().unwrap_or(0))
+            .sum()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.sides_present.contains(&true) {
+            return Err("At least one side needs a strip".into());
+        }
+        for (index, side) in SIDES.iter().enumerate() {
+            if self.sides_present[index] && self.led_counts[index].trim().parse::<usize>().is_err() {
+                return Err(format!("The number of LEDs on the {} has to be a whole number", side.to_string().to_lowercase()));
+            }
+            if !self.bezel_thickness[index].trim().parse::<f32>().is_ok_and(|thickness| thickness >= 0.0) {
+                return Err(format!("The {} bezel has to be a thickness in inches", side.to_string().to_lowercase()));
+            }
+        }
+        if !self.diagonal_size.trim().parse::<f32>().is_ok_and(|size| size > 0.0) {
+            return Err("The screen size has to be a diagonal in inches".into());
+        }
+        if !self.leds_per_inch.trim().parse::<f32>().is_ok_and(|density| density > 0.0) {
+            return Err("LEDs per inch has to be more than 0".into());
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,253 @@
-use crate::side::SideDirection;
+extern crate iced;
 
-struct State{
-    side_configuration: Vec<SideDirection>
+use self::iced::{Color, Point, Rectangle, Size, mouse};
+use self::iced::alignment::{Horizontal, Vertical};
+use self::iced::canvas::{self, Cache, Cursor, Event, Frame, Geometry, Path, Program, Stroke};
+use self::iced::canvas::event::Status;
+use crate::monitor_configurer::{Corner, Message, side_index};
+use crate::side::{Side, SideDirection};
 
-}
\ No newline at end of file
+// space around the screen for the numbers of the LEDs along its edges
+const MARGIN: f32 = 30.0;
+const LED_RADIUS: f32 = 5.0;
+const START_RADIUS: f32 = 9.0;
+// how far from an LED a click still counts as clicking it
+const HIT_DISTANCE: f32 = 12.0;
+// the same colours as the side_bars test pattern so the canvas and the strip can be compared
+const SIDE_COLOURS: [Color; 4] = [
+    Color { r: 0.9, g: 0.1, b: 0.1, a: 1.0 },
+    Color { r: 0.9, g: 0.8, b: 0.1, a: 1.0 },
+    Color { r: 0.1, g: 0.3, b: 0.9, a: 1.0 },
+    Color { r: 0.1, g: 0.7, b: 0.2, a: 1.0 },
+];
+
+pub struct State{
+    side_configuration: Vec<SideDirection>,
+    // where each LED is sampled on the screen and which side it's on, in chain order
+    pixel_locations: Vec<[usize; 2]>,
+    led_sides: Vec<Side>,
+    // where this monitor's LEDs start in the whole chain, so the labels match the identify descriptions
+    first_led: usize,
+    screen_size: (usize, usize),
+    // where the start marker is while it's being dragged to another corner
+    dragged_start: Option<Point>,
+    // the LED being lit on the strip to identify it
+    highlighted_led: Option<usize>,
+    cache: Cache,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            side_configuration: Vec::new(),
+            pixel_locations: Vec::new(),
+            led_sides: Vec::new(),
+            first_led: 0,
+            screen_size: (1920, 1080),
+            dragged_start: None,
+            highlighted_led: None,
+            cache: Cache::default(),
+        }
+    }
+}
+
+impl State {
+    pub fn set_layout(&mut self, side_configuration: Vec<SideDirection>, pixel_locations: Vec<[usize; 2]>, led_sides: Vec<Side>, screen_size: (usize, usize), first_led: usize) {
+        self.side_configuration = side_configuration;
+        self.pixel_locations = pixel_locations;
+        self.led_sides = led_sides;
+        self.first_led = first_led;
+        self.screen_size = (screen_size.0.max(1), screen_size.1.max(1));
+        self.cache.clear();
+    }
+
+    pub fn set_highlighted_led(&mut self, highlighted_led: Option<usize>) {
+        self.highlighted_led = highlighted_led;
+    }
+
+    fn screen_rectangle(&self, bounds: Size) -> Rectangle {
+        // the largest rectangle with the screen's aspect ratio that fits inside the margins, centred
+        let (screen_width, screen_height) = (self.screen_size.0 as f32, self.screen_size.1 as f32);
+        let scale = ((bounds.width - MARGIN * 2.0) / screen_width).min((bounds.height - MARGIN * 2.0) / screen_height).max(0.0);
+        let size = Size::new(screen_width * scale, screen_height * scale);
+        Rectangle::new(Point::new((bounds.width - size.width) / 2.0, (bounds.height - size.height) / 2.0), size)
+    }
+
+    fn to_canvas(&self, screen: &Rectangle, location: [usize; 2]) -> Point {
+        Point::new(
+            screen.x + location[0] as f32 / self.screen_size.0 as f32 * screen.width,
+            screen.y + location[1] as f32 / self.screen_size.1 as f32 * screen.height
+        )
+    }
+
+    fn led_at(&self, screen: &Rectangle, position: Point) -> Option<usize> {
+        self.pixel_locations.iter()
+            .map(|location| self.to_canvas(screen, *location).distance(position))
+            .enumerate()
+            .filter(|(_led, distance)| *distance <= HIT_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(led, _distance)| led)
+    }
+
+    fn draw_layout(&self, frame: &mut Frame) {
+        let screen = self.screen_rectangle(frame.size());
+        frame.fill(&Path::rectangle(screen.position(), screen.size()), Color::from_rgb(0.15, 0.15, 0.15));
+        frame.stroke(&Path::rectangle(screen.position(), screen.size()), Stroke::default().with_color(Color::BLACK).with_width(2.0));
+
+        let centre = screen.center();
+        for strip in &self.side_configuration {
+            // an arrow a little inside the middle of each side shows which way its LEDs are counted
+            let (middle, step) = match strip.side {
+                Side::TOP => (Point::new(centre.x, screen.y + 25.0), [1.0, 0.0]),
+                Side::BOTTOM => (Point::new(centre.x, screen.y + screen.height - 25.0), [1.0, 0.0]),
+                Side::LEFT => (Point::new(screen.x + 25.0, centre.y), [0.0, 1.0]),
+                Side::RIGHT => (Point::new(screen.x + screen.width - 25.0, centre.y), [0.0, 1.0]),
+                Side::ERROR => continue,
+            };
+            let sign = match strip.direction {
+                Side::RIGHT | Side::BOTTOM => 1.0,
+                _ => -1.0,
+            };
+            let (dx, dy) = (step[0] * sign * 20.0, step[1] * sign * 20.0);
+            let tip = Point::new(middle.x + dx, middle.y + dy);
+            let arrow = Path::new(|builder| {
+                builder.move_to(Point::new(middle.x - dx, middle.y - dy));
+                builder.line_to(tip);
+                builder.move_to(Point::new(tip.x - dx * 0.4 - dy * 0.4, tip.y - dy * 0.4 - dx * 0.4));
+                builder.line_to(tip);
+                builder.line_to(Point::new(tip.x - dx * 0.4 + dy * 0.4, tip.y - dy * 0.4 + dx * 0.4));
+            });
+            let colour = side_index(strip.side).map_or(Color::WHITE, |index| SIDE_COLOURS[index]);
+            frame.stroke(&arrow, Stroke::default().with_color(colour).with_width(2.0));
+        }
+
+        for (led, (location, side)) in self.pixel_locations.iter().zip(&self.led_sides).enumerate() {
+            let position = self.to_canvas(&screen, *location);
+            let colour = side_index(*side).map_or(Color::WHITE, |index| SIDE_COLOURS[index]);
+            frame.fill(&Path::circle(position, LED_RADIUS), colour);
+
+            // numbers sit outside the screen so they don't cover the dots next to them
+            let (label_offset, horizontal_alignment, vertical_alignment) = match side {
+                Side::TOP => (Point::new(0.0, -LED_RADIUS - 2.0), Horizontal::Center, Vertical::Bottom),
+                Side::BOTTOM => (Point::new(0.0, LED_RADIUS + 2.0), Horizontal::Center, Vertical::Top),
+                Side::LEFT => (Point::new(-LED_RADIUS - 2.0, 0.0), Horizontal::Right, Vertical::Center),
+                Side::RIGHT => (Point::new(LED_RADIUS + 2.0, 0.0), Horizontal::Left, Vertical::Center),
+                Side::ERROR => (Point::ORIGIN, Horizontal::Center, Vertical::Center),
+            };
+            frame.fill_text(canvas::Text {
+                content: (self.first_led + led + 1).to_string(),
+                position: Point::new(position.x + label_offset.x, position.y + label_offset.y),
+                color: Color::BLACK,
+                size: 12.0,
+                horizontal_alignment,
+                vertical_alignment,
+                ..canvas::Text::default()
+            });
+        }
+
+        frame.fill_text(canvas::Text {
+            content: if self.pixel_locations.is_empty() {
+                String::from("Fill in the fields below to see where the LEDs are")
+            } else {
+                String::from("Drag the start to another corner, click a side to flip its direction")
+            },
+            position: centre,
+            color: Color::WHITE,
+            size: 16.0,
+            horizontal_alignment: Horizontal::Center,
+            vertical_alignment: Vertical::Center,
+            ..canvas::Text::default()
+        });
+    }
+
+    fn draw_highlight(&self, frame: &mut Frame) {
+        let screen = self.screen_rectangle(frame.size());
+        if let Some(location) = self.highlighted_led.and_then(|led| self.pixel_locations.get(led)) {
+            let position = self.to_canvas(&screen, *location);
+            frame.fill(&Path::circle(position, LED_RADIUS), Color::WHITE);
+            frame.stroke(&Path::circle(position, START_RADIUS + 4.0), Stroke::default().with_color(Color::WHITE).with_width(2.0));
+        }
+    }
+
+    fn draw_start(&self, frame: &mut Frame) {
+        let screen = self.screen_rectangle(frame.size());
+        let position = match (self.dragged_start, self.pixel_locations.first()) {
+            (Some(position), _) => position,
+            (None, Some(location)) => self.to_canvas(&screen, *location),
+            (None, None) => return,
+        };
+        frame.stroke(&Path::circle(position, START_RADIUS), Stroke::default().with_color(Color::WHITE).with_width(3.0));
+    }
+
+    fn nearest_corner(screen: &Rectangle, position: Point) -> Corner {
+        let corners = [
+            (Corner::TopLeft, Point::new(screen.x, screen.y)),
+            (Corner::TopRight, Point::new(screen.x + screen.width, screen.y)),
+            (Corner::BottomRight, Point::new(screen.x + screen.width, screen.y + screen.height)),
+            (Corner::BottomLeft, Point::new(screen.x, screen.y + screen.height)),
+        ];
+        corners.iter()
+            .min_by(|a, b| a.1.distance(position).total_cmp(&b.1.distance(position)))
+            .map_or(Corner::default(), |(corner, _point)| *corner)
+    }
+}
+
+impl Program<Message> for State {
+    fn update(&mut self, event: Event, bounds: Rectangle, cursor: Cursor) -> (Status, Option<Message>) {
+        let position = match cursor.position_in(&bounds) {
+            Some(position) => position,
+            None => {
+                // letting go outside the canvas puts the start back where it was
+                if self.dragged_start.take().is_some() {
+                    self.cache.clear();
+                }
+                return (Status::Ignored, None);
+            }
+        };
+        let screen = self.screen_rectangle(bounds.size());
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                match self.led_at(&screen, position) {
+                    Some(0) => {
+                        self.dragged_start = Some(position);
+                        (Status::Captured, None)
+                    }
+                    Some(led) => {
+                        let side = self.led_sides.get(led).and_then(|side| side_index(*side));
+                        (Status::Captured, side.map(Message::SideFlipped))
+                    }
+                    None => (Status::Ignored, None),
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) if self.dragged_start.is_some() => {
+                self.dragged_start = Some(position);
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if self.dragged_start.is_some() => {
+                self.dragged_start = None;
+                (Status::Captured, Some(Message::StartCornerSelected(State::nearest_corner(&screen, position))))
+            }
+            _ => (Status::Ignored, None),
+        }
+    }
+
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        // the layout only changes with the fields, the start marker moves with every drag
+        let layout = self.cache.draw(bounds.size(), |frame| self.draw_layout(frame));
+        let mut overlay = Frame::new(bounds.size());
+        self.draw_highlight(&mut overlay);
+        self.draw_start(&mut overlay);
+        vec![layout, overlay.into_geometry()]
+    }
+
+    fn mouse_interaction(&self, bounds: Rectangle, cursor: Cursor) -> mouse::Interaction {
+        if self.dragged_start.is_some() {
+            return mouse::Interaction::Grabbing;
+        }
+        match cursor.position_in(&bounds).and_then(|position| self.led_at(&self.screen_rectangle(bounds.size()), position)) {
+            Some(0) => mouse::Interaction::Grab,
+            Some(_) => mouse::Interaction::Pointer,
+            None => mouse::Interaction::default(),
+        }
+    }
+}
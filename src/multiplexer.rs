@@ -0,0 +1,194 @@
+//Chooses which colour input drives the LEDs, the highest priority input that hasn't timed out wins
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use crate::effects::{EffectEngine, EffectKind, LedPlacement};
+
+// the selected output mode (capture or an effect) sits below anything sent in from outside
+pub const MODE_PRIORITY: u8 = 100;
+pub const NETWORK_PRIORITY: u8 = 150;
+pub const CONTROL_API_PRIORITY: u8 = 200;
+// identifying LEDs has to win over everything else or the wrong LEDs light up
+pub const CALIBRATION_PRIORITY: u8 = 250;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputSource {
+    Capture,
+    Effect,
+    ControlApi,
+    Network,
+    Audio,
+    Calibration,
+}
+
+impl Display for InputSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                InputSource::Capture => "capture",
+                InputSource::Effect => "effect",
+                InputSource::ControlApi => "control_api",
+                InputSource::Network => "network",
+                InputSource::Audio => "audio",
+                InputSource::Calibration => "calibration"
+            }
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputContent {
+    // RGB bytes in LED order, padded with black or cut off to fit the number of LEDs, except while calibrating
+    Colours(Vec<u8>),
+    SolidColour([u8; 3]),
+    Effect(EffectKind),
+}
+
+struct ColourInput {
+    source: InputSource,
+    priority: u8,
+    content: InputContent,
+    expires: Option<Instant>,
+}
+
+pub struct Multiplexer {
+    inputs: Vec<ColourInput>,
+    transition_duration: Duration,
+    active_source: Option<InputSource>,
+    transition_from: Vec<u8>,
+    transition_start: Instant,
+    // the winning input's colours this frame and what was output last frame, both reused between frames
+    target: Vec<u8>,
+    last_output: Vec<u8>,
+}
+
+impl Multiplexer {
+    pub fn new(transition_duration: Duration) -> Multiplexer {
+        Multiplexer {
+            inputs: Vec::new(),
+            transition_duration,
+            active_source: None,
+            transition_from: Vec::new(),
+            transition_start: Instant::now(),
+            target: Vec::new(),
+            last_output: Vec::new()
+        }
+    }
+
+    pub fn set_transition_duration(&mut self, transition_duration: Duration) {
+        self.transition_duration = transition_duration;
+    }
+
+    pub fn set_input(&mut self, source: InputSource, priority: u8, content: InputContent, timeout: Option<Duration>) {
+        /*
+        Each source has a single slot, setting it again replaces the previous content, priority and timeout
+        */
+        let expires = timeout.map(|timeout| Instant::now() + timeout);
+        match self.inputs.iter_mut().find(|input| input.source == source) {
+            Some(input) => {
+                input.priority = priority;
+                input.content = content;
+                input.expires = expires;
+            },
+            None => self.inputs.push(ColourInput {
+                source,
+                priority,
+                content,
+                expires
+            })
+        }
+    }
+
+    pub fn set_colours(&mut self, source: InputSource, priority: u8, colours: &[u8]) {
+        /*
+        Like set_input with colours and no timeout, but reuses the slot's buffer for inputs that update every frame
+        */
+        match self.inputs.iter_mut().find(|input| input.source == source) {
+            Some(input) => {
+                input.priority = priority;
+                input.expires = None;
+                match &mut input.content {
+                    InputContent::Colours(existing_colours) => {
+                        existing_colours.clear();
+                        existing_colours.extend_from_slice(colours);
+                    },
+                    content => *content = InputContent::Colours(colours.to_vec())
+                }
+            },
+            None => self.set_input(source, priority, InputContent::Colours(colours.to_vec()), None)
+        }
+    }
+
+    pub fn clear_input(&mut self, source: InputSource) {
+        self.inputs.retain(|input| input.source != source);
+    }
+
+    pub fn active_input(&self) -> Option<(InputSource, u8)> {
+        Multiplexer::highest_priority_input(&self.inputs).map(|input| (input.source, input.priority))
+    }
+
+    fn highest_priority_input(inputs: &[ColourInput]) -> Option<&ColourInput> {
+        /*
+        On a tie the source whose slot was created last wins. Setting a source again updates its slot in place
+        and doesn't change which one wins, only clearing it and setting it anew does.
+        Takes the inputs rather than self so output can fill its buffers while holding on to the winner
+        */
+        inputs.iter().max_by_key(|input| input.priority)
+    }
+
+    pub fn output(&mut self, effect_engine: &EffectEngine, leds: &[LedPlacement], output: &mut Vec<u8>) -> bool {
+        /*
+        Writes this frame's colours into output, returns false and leaves output alone when there's no input.
+        Called every frame so nothing is allocated once the buffers have grown to fit the LEDs
+        */
+        let now = Instant::now();
+        self.inputs.retain(|input| !matches!(input.expires, Some(expires) if expires <= now));
+
+        let input = match Multiplexer::highest_priority_input(&self.inputs) {
+            Some(input) => input,
+            None => return false
+        };
+        let source = input.source;
+        self.target.clear();
+        match &input.content {
+            InputContent::Colours(colours) => {
+                self.target.extend_from_slice(colours);
+                // identifying LEDs lights the layout being edited, which can have more or fewer LEDs than the saved one
+                if source != InputSource::Calibration {
+                    self.target.resize(leds.len() * 3, 0);
+                }
+            },
+            InputContent::SolidColour(colour) => {
+                for _ in leds {
+                    self.target.extend_from_slice(colour);
+                }
+            },
+            InputContent::Effect(kind) => effect_engine.render(*kind, leds, &mut self.target)
+        };
+
+        if self.active_source != Some(source) {
+            // blend from whatever was shown last into the new input
+            self.active_source = Some(source);
+            self.transition_from.clear();
+            self.transition_from.extend_from_slice(&self.last_output);
+            self.transition_start = now;
+        }
+
+        let progress = if self.transition_duration.is_zero() {
+            1.0
+        } else {
+            now.duration_since(self.transition_start).as_secs_f32() / self.transition_duration.as_secs_f32()
+        };
+        output.clear();
+        if progress < 1.0 && self.transition_from.len() == self.target.len() {
+            output.extend(self.transition_from.iter().zip(self.target.iter())
+                .map(|(from, to)| (*from as f32 + (*to as f32 - *from as f32) * progress) as u8));
+        } else {
+            output.extend_from_slice(&self.target);
+        }
+        self.last_output.clear();
+        self.last_output.extend_from_slice(output);
+        true
+    }
+}
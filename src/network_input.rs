@@ -0,0 +1,49 @@
+//Receives raw RGB packets over UDP and feeds them to the worker as a prioritised input
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::{thread, time};
+use crate::multiplexer::{InputContent, InputSource};
+use crate::worker::ControlMessage;
+
+// how long the last packet keeps being shown once packets stop arriving
+const PACKET_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+// largest payload a UDP packet can carry
+const MAX_PACKET_SIZE: usize = 65507;
+
+pub fn spawn_udp_input(address: &str, priority: u8, worker_controller: Arc<Mutex<Sender<ControlMessage>>>) {
+    /*
+    Each packet is the RGB bytes for every LED in chain order, the same layout that is written to the serial port
+    */
+    let socket = match UdpSocket::bind(address) {
+        Ok(socket) => socket,
+        Err(error) => {
+            eprintln!("Unable to listen for LED colours on {}: {}", address, error);
+            return
+        }
+    };
+    println!("Listening for LED colours on udp://{}", address);
+
+    thread::spawn(move || {
+        let mut packet = vec![0; MAX_PACKET_SIZE];
+        loop {
+            let packet_length = match socket.recv_from(&mut packet) {
+                Ok((packet_length, _sender)) => packet_length,
+                Err(error) => {
+                    eprintln!("Failed to receive LED colours: {}", error);
+                    continue
+                }
+            };
+            let message = ControlMessage::SetInput{
+                source: InputSource::Network,
+                priority,
+                content: InputContent::Colours(packet[..packet_length].to_vec()),
+                timeout: Some(PACKET_TIMEOUT)
+            };
+            if worker_controller.lock().unwrap().send(message).is_err() {
+                // the worker has stopped
+                break
+            }
+        }
+    });
+}
@@ -0,0 +1,185 @@
+//Window showing the colours the worker sends to the LEDs at the places they're sampled from
+extern crate iced;
+
+use self::iced::{Application, Canvas, Color, Command, Element, Length, Point, Rectangle, Settings, Size, Subscription, executor, time};
+use self::iced::alignment::{Horizontal, Vertical};
+use self::iced::canvas::{self, Cursor, Frame, Geometry, Path, Program, Stroke};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, mpsc};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+use crate::settings_configurer::SettingsConfigurer;
+use crate::worker::{ControlMessage, OutputMode, WorkerStatus};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(33);
+// the layout and sampling time change rarely compared to the colours so they're asked for less often
+const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+// frames queued for the preview between redraws, more than this are dropped by the worker
+const FRAME_QUEUE_LENGTH: usize = 64;
+const MARGIN: f32 = 40.0;
+const LED_RADIUS: f32 = 6.0;
+
+pub struct PreviewFlags {
+    pub worker_controller: Arc<Mutex<Sender<ControlMessage>>>,
+}
+
+pub struct Preview {
+    worker_controller: Arc<Mutex<Sender<ControlMessage>>>,
+    output_receiver: Receiver<Arc<[u8]>>,
+    // the reply to the last status request, if it hasn't arrived yet
+    status_receiver: Option<Receiver<WorkerStatus>>,
+    last_status_request: Instant,
+    status: Option<WorkerStatus>,
+    colours: Vec<u8>,
+    // when each frame in the last second arrived
+    frame_times: VecDeque<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Tick(Instant),
+}
+
+pub fn run(worker_controller: Arc<Mutex<Sender<ControlMessage>>>, icon_path: Option<&str>) -> iced::Result {
+    let window_defaults = SettingsConfigurer::default_window_settings(icon_path);
+    let mut settings = Settings::with_flags(PreviewFlags { worker_controller });
+    settings.window = window_defaults.window;
+    settings.window.size = (960, 600);
+    settings.window.resizable = true;
+    Preview::run(settings)
+}
+
+impl Application for Preview {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Flags = PreviewFlags;
+
+    fn new(flags: PreviewFlags) -> (Self, Command<Message>) {
+        let (output_sender, output_receiver) = mpsc::sync_channel(FRAME_QUEUE_LENGTH);
+        let _ = flags.worker_controller.lock().unwrap().send(ControlMessage::SubscribeOutput(output_sender));
+        let mut preview = Preview {
+            worker_controller: flags.worker_controller,
+            output_receiver,
+            status_receiver: None,
+            last_status_request: Instant::now(),
+            status: None,
+            colours: Vec::new(),
+            frame_times: VecDeque::new(),
+        };
+        preview.request_status();
+        (preview, Command::none())
+    }
+
+    fn title(&self) -> String {
+        String::from("Ambient Lighting Preview")
+    }
+
+    fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::Tick(now) => {
+                // only the newest frame is drawn but every one counts towards the frame rate
+                while let Ok(colours) = self.output_receiver.try_recv() {
+                    self.colours.clear();
+                    self.colours.extend_from_slice(&colours);
+                    self.frame_times.push_back(Instant::now());
+                }
+                while self.frame_times.front().is_some_and(|time| now.duration_since(*time) > Duration::from_secs(1)) {
+                    self.frame_times.pop_front();
+                }
+
+                if let Some(status_receiver) = &self.status_receiver {
+                    match status_receiver.try_recv() {
+                        Ok(status) => {
+                            self.status = Some(status);
+                            self.status_receiver = None;
+                        }
+                        Err(TryRecvError::Disconnected) => self.status_receiver = None,
+                        Err(TryRecvError::Empty) => {}
+                    }
+                }
+                if self.status_receiver.is_none() && now.duration_since(self.last_status_request) >= STATUS_INTERVAL {
+                    self.request_status();
+                }
+            }
+        }
+        Command::none()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        time::every(REDRAW_INTERVAL).map(Message::Tick)
+    }
+
+    fn view(&mut self) -> Element<'_, Message> {
+        Canvas::new(self)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+}
+
+impl Preview {
+    fn request_status(&mut self) {
+        let (status_sender, status_receiver) = mpsc::channel();
+        if self.worker_controller.lock().unwrap().send(ControlMessage::RequestStatus(status_sender)).is_ok() {
+            self.status_receiver = Some(status_receiver);
+        }
+        self.last_status_request = Instant::now();
+    }
+
+    fn overlay_text(&self) -> String {
+        let led_count = self.colours.len() / 3;
+        match &self.status {
+            // effects don't sample the screen so the last sampling time would be misleading
+            Some(status) if status.output_mode == OutputMode::Capture => format!(
+                "{} FPS, sampling {:.2} ms, {} LEDs, {}",
+                self.frame_times.len(), status.sampling_time.as_secs_f64() * 1000.0, led_count, status.state
+            ),
+            Some(status) => format!("{} FPS, {} LEDs, {}, {}", self.frame_times.len(), led_count, status.output_mode, status.state),
+            None => format!("{} FPS, {} LEDs", self.frame_times.len(), led_count),
+        }
+    }
+}
+
+impl Program<Message> for Preview {
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+        frame.fill(&Path::rectangle(Point::ORIGIN, bounds.size()), Color::from_rgb(0.08, 0.08, 0.08));
+
+        match &self.status {
+            Some(status) if status.screen_width > 0 && status.screen_height > 0 => {
+                // the largest rectangle with the screen's aspect ratio that fits inside the margins, centred
+                let (screen_width, screen_height) = (status.screen_width as f32, status.screen_height as f32);
+                let scale = ((bounds.width - MARGIN * 2.0) / screen_width).min((bounds.height - MARGIN * 2.0) / screen_height).max(0.0);
+                let size = Size::new(screen_width * scale, screen_height * scale);
+                let origin = Point::new((bounds.width - size.width) / 2.0, (bounds.height - size.height) / 2.0);
+                frame.fill(&Path::rectangle(origin, size), Color::from_rgb(0.2, 0.2, 0.2));
+                frame.stroke(&Path::rectangle(origin, size), Stroke::default().with_color(Color::BLACK).with_width(2.0));
+
+                for (location, colour) in status.pixel_locations.iter().zip(self.colours.chunks_exact(3)) {
+                    let position = Point::new(origin.x + location[0] as f32 * scale, origin.y + location[1] as f32 * scale);
+                    frame.fill(&Path::circle(position, LED_RADIUS), Color::from_rgb8(colour[0], colour[1], colour[2]));
+                }
+            }
+            _ => {
+                frame.fill_text(canvas::Text {
+                    content: String::from("Waiting for the worker"),
+                    position: Point::new(bounds.width / 2.0, bounds.height / 2.0),
+                    color: Color::WHITE,
+                    size: 20.0,
+                    horizontal_alignment: Horizontal::Center,
+                    vertical_alignment: Vertical::Center,
+                    ..canvas::Text::default()
+                });
+            }
+        }
+
+        frame.fill_text(canvas::Text {
+            content: self.overlay_text(),
+            position: Point::new(10.0, 10.0),
+            color: Color::WHITE,
+            size: 16.0,
+            ..canvas::Text::default()
+        });
+        vec![frame.into_geometry()]
+    }
+}
@@ -3,17 +3,53 @@ extern crate serialport;
 
 use std::fmt::{Display, Formatter, Result};
 use std::{path, fs};
-use std::io::{Read, Write};
+use std::io::{self, Read};
 use json::object;
 use serialport::{SerialPort, DataBits, StopBits, Parity, FlowControl};
+use crate::audio::AudioInputSettings;
 use crate::baudrate::Baudrate;
+use crate::colour_correction::ColourSettings;
+use crate::frame_source::FrameSourceSettings;
+use crate::frame_source::raw_video::PixelFormat;
 use crate::framerate::FramerateLimiter;
+use crate::effects::{self, EffectSettings};
+use crate::multiplexer::NETWORK_PRIORITY;
+use crate::worker::{OutputMode, WorkerState};
+
+// the settings the lights start with and reload
+pub const CONFIGURATION_PATH: &str = "assets/program_configuration.json";
+// each file in here is a whole program configuration that can be switched to from the tray
+pub const PROFILE_DIRECTORY: &str = "assets/profiles";
 
 #[derive(Clone, PartialEq)]
 pub struct ProgramConfiguration {
     pub serial_port: String,
     pub refresh_rate: f32,
     pub baudrate: Baudrate,
+    pub brightness: f32,
+    pub colour_settings: ColourSettings,
+    pub http_address: Option<String>,
+    pub worker_state: WorkerState,
+    pub blank_on_exit: bool,
+    // seconds taken to fade the LEDs to black when turning off or quitting
+    pub fade_duration: f32,
+    // pausing fades to black too instead of holding the last frame
+    pub fade_on_pause: bool,
+    pub output_mode: OutputMode,
+    pub effect_settings: EffectSettings,
+    // seconds taken to blend between inputs when a higher priority one starts or stops
+    pub transition_duration: f32,
+    // address to receive raw RGB packets on, these override capture and effects while they keep arriving
+    pub udp_input_address: Option<String>,
+    pub udp_input_priority: u8,
+    pub audio_settings: AudioInputSettings,
+    pub frame_source: FrameSourceSettings,
+    // LED colours are written here instead of the serial port when set
+    pub output_file: Option<String>,
+    // captured frames are shrunk by this factor before sampling to save CPU, 1 samples at full resolution
+    pub capture_downscale: usize,
+    // threads the LEDs are sampled on, 1 keeps sampling on the worker thread
+    pub sampling_threads: usize,
 }
 
 impl Default for ProgramConfiguration {
@@ -21,14 +57,53 @@ impl Default for ProgramConfiguration {
         ProgramConfiguration {
             serial_port: String::from("COM0"),
             refresh_rate: 20.0,
-            baudrate: Baudrate::default()
+            baudrate: Baudrate::default(),
+            brightness: 1.0,
+            colour_settings: ColourSettings::default(),
+            http_address: None,
+            worker_state: WorkerState::default(),
+            blank_on_exit: false,
+            fade_duration: 1.0,
+            fade_on_pause: false,
+            output_mode: OutputMode::default(),
+            effect_settings: EffectSettings::default(),
+            transition_duration: 0.5,
+            udp_input_address: None,
+            udp_input_priority: NETWORK_PRIORITY,
+            audio_settings: AudioInputSettings::default(),
+            frame_source: FrameSourceSettings::default(),
+            output_file: None,
+            capture_downscale: 1,
+            sampling_threads: 1,
         }
     }
 }
 
 impl Display for ProgramConfiguration {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "ProgramConfig{{serial_port: {}, refresh_rate: {}, baudrate: {}}}", self.serial_port, self.refresh_rate, self.baudrate)
+        // one field per line, there are too many to read on one
+        writeln!(f, "ProgramConfig{{")?;
+        writeln!(f, "    serial_port: {}", self.serial_port)?;
+        writeln!(f, "    refresh_rate: {}", self.refresh_rate)?;
+        writeln!(f, "    baudrate: {}", self.baudrate)?;
+        writeln!(f, "    brightness: {}", self.brightness)?;
+        writeln!(f, "    colour_settings: {}", self.colour_settings)?;
+        writeln!(f, "    http_address: {:?}", self.http_address)?;
+        writeln!(f, "    worker_state: {}", self.worker_state)?;
+        writeln!(f, "    blank_on_exit: {}", self.blank_on_exit)?;
+        writeln!(f, "    fade_duration: {}", self.fade_duration)?;
+        writeln!(f, "    fade_on_pause: {}", self.fade_on_pause)?;
+        writeln!(f, "    output_mode: {}", self.output_mode.name())?;
+        writeln!(f, "    effect_settings: {:?}", self.effect_settings)?;
+        writeln!(f, "    transition_duration: {}", self.transition_duration)?;
+        writeln!(f, "    udp_input_address: {:?}", self.udp_input_address)?;
+        writeln!(f, "    udp_input_priority: {}", self.udp_input_priority)?;
+        writeln!(f, "    audio_settings: {:?}", self.audio_settings)?;
+        writeln!(f, "    frame_source: {:?}", self.frame_source)?;
+        writeln!(f, "    output_file: {:?}", self.output_file)?;
+        writeln!(f, "    capture_downscale: {}", self.capture_downscale)?;
+        writeln!(f, "    sampling_threads: {}", self.sampling_threads)?;
+        write!(f, "}}")
     }
 }
 
@@ -58,32 +133,160 @@ impl ProgramConfiguration {
         ProgramConfiguration::try_load(path_in).unwrap_or_default()
     }
 
-    fn try_load(path_in: &str) -> Option<ProgramConfiguration>{
+    pub fn load_profile(name: &str) -> Option<ProgramConfiguration> {
+        // unlike the main configuration a broken profile isn't quietly swapped for the defaults
+        let profile_path = path::Path::new(PROFILE_DIRECTORY).join(format!("{}.json", name));
+        ProgramConfiguration::try_load(profile_path.to_str()?)
+    }
+
+    pub fn try_load(path_in: &str) -> Option<ProgramConfiguration>{
         let file_path = path::Path::new(path_in);
         let mut open_file = fs::File::open(file_path).ok()?;
         let mut file_contents = String::new();
-        open_file.read_to_string(&mut file_contents).expect(format!("Could not read file {}", path_in).as_str());
+        open_file.read_to_string(&mut file_contents).ok()?;
 
         let parsed_json = json::parse(file_contents.as_str()).ok()?;
 
         let out_config = ProgramConfiguration {
             serial_port: String::from(parsed_json["serial_port"].as_str()?),
             refresh_rate: parsed_json["refresh_rate"].as_f32()?,
-            baudrate: Baudrate::from(parsed_json["baud_rate"].as_u32()?)
+            baudrate: Baudrate::from(parsed_json["baud_rate"].as_u32()?),
+            // optional fields fall back to their defaults so older configuration files still load
+            brightness: parsed_json["brightness"].as_f32().unwrap_or(1.0),
+            colour_settings: ProgramConfiguration::parse_colour_settings(&parsed_json),
+            http_address: parsed_json["http_address"].as_str().map(String::from),
+            worker_state: parsed_json["worker_state"].as_str().and_then(WorkerState::from_name).unwrap_or_default(),
+            blank_on_exit: parsed_json["blank_on_exit"].as_bool().unwrap_or(false),
+            fade_duration: parsed_json["fade_duration"].as_f32().unwrap_or(1.0),
+            fade_on_pause: parsed_json["fade_on_pause"].as_bool().unwrap_or(false),
+            output_mode: parsed_json["output_mode"].as_str().and_then(OutputMode::from_name).unwrap_or_default(),
+            effect_settings: ProgramConfiguration::parse_effect_settings(&parsed_json),
+            transition_duration: parsed_json["transition_duration"].as_f32().unwrap_or(0.5),
+            udp_input_address: parsed_json["udp_input_address"].as_str().map(String::from),
+            udp_input_priority: parsed_json["udp_input_priority"].as_u8().unwrap_or(NETWORK_PRIORITY),
+            audio_settings: ProgramConfiguration::parse_audio_settings(&parsed_json),
+            frame_source: ProgramConfiguration::parse_frame_source(&parsed_json),
+            output_file: parsed_json["output_file"].as_str().map(String::from),
+            capture_downscale: parsed_json["capture_downscale"].as_usize().unwrap_or(1),
+            sampling_threads: parsed_json["sampling_threads"].as_usize().unwrap_or(1),
         };
         Some(out_config)
     }
 
-    pub fn save_to_file(&self, path_in: &str){
+    fn parse_effect_settings(parsed_json: &json::JsonValue) -> EffectSettings {
+        let defaults = EffectSettings::default();
+        EffectSettings {
+            colour: parsed_json["effect_colour"].as_str().and_then(effects::parse_colour).unwrap_or(defaults.colour),
+            secondary_colour: parsed_json["effect_secondary_colour"].as_str().and_then(effects::parse_colour).unwrap_or(defaults.secondary_colour),
+            speed: parsed_json["effect_speed"].as_f32().unwrap_or(defaults.speed),
+        }
+    }
+
+    fn parse_colour_settings(parsed_json: &json::JsonValue) -> ColourSettings {
+        let defaults = ColourSettings::default();
+        ColourSettings {
+            gamma: parsed_json["gamma"].as_f32().unwrap_or(defaults.gamma),
+            saturation: parsed_json["saturation"].as_f32().unwrap_or(defaults.saturation),
+            white_balance: [
+                parsed_json["white_balance_red"].as_f32().unwrap_or(defaults.white_balance[0]),
+                parsed_json["white_balance_green"].as_f32().unwrap_or(defaults.white_balance[1]),
+                parsed_json["white_balance_blue"].as_f32().unwrap_or(defaults.white_balance[2]),
+            ],
+            smoothing: parsed_json["smoothing"].as_f32().unwrap_or(defaults.smoothing),
+        }
+    }
+
+    fn parse_audio_settings(parsed_json: &json::JsonValue) -> AudioInputSettings {
+        let defaults = AudioInputSettings::default();
+        AudioInputSettings {
+            input: parsed_json["audio_input"].as_str().map(String::from).unwrap_or(defaults.input),
+            sample_rate: parsed_json["audio_sample_rate"].as_u32().unwrap_or(defaults.sample_rate),
+            channels: parsed_json["audio_channels"].as_u16().unwrap_or(defaults.channels),
+        }
+    }
+
+    fn parse_frame_source(parsed_json: &json::JsonValue) -> FrameSourceSettings {
+        let defaults = FrameSourceSettings::default();
+        FrameSourceSettings {
+            input: parsed_json["frame_source"].as_str().map(String::from).unwrap_or(defaults.input),
+            frame_rate: parsed_json["frame_source_rate"].as_f32().unwrap_or(defaults.frame_rate),
+            width: parsed_json["frame_source_width"].as_usize().unwrap_or(defaults.width),
+            height: parsed_json["frame_source_height"].as_usize().unwrap_or(defaults.height),
+            pixel_format: parsed_json["frame_source_pixel_format"].as_str().and_then(PixelFormat::from_name).unwrap_or(defaults.pixel_format),
+        }
+    }
+
+    pub fn save_to_file(&self, path_in: &str) -> io::Result<()> {
         let file_path = path::Path::new(path_in);
-        let mut open_file = fs::File::create(file_path).unwrap();
 
         let json_out = object!{
             "refresh_rate": self.refresh_rate,
             "baud_rate": self.baudrate as u32,
-            "serial_port": self.serial_port.as_str()
+            "serial_port": self.serial_port.as_str(),
+            "brightness": self.brightness,
+            "gamma": self.colour_settings.gamma,
+            "saturation": self.colour_settings.saturation,
+            "white_balance_red": self.colour_settings.white_balance[0],
+            "white_balance_green": self.colour_settings.white_balance[1],
+            "white_balance_blue": self.colour_settings.white_balance[2],
+            "smoothing": self.colour_settings.smoothing,
+            "http_address": self.http_address.clone(),
+            "worker_state": self.worker_state.to_string(),
+            "blank_on_exit": self.blank_on_exit,
+            "fade_duration": self.fade_duration,
+            "fade_on_pause": self.fade_on_pause,
+            "output_mode": self.output_mode.name(),
+            "effect_colour": effects::format_colour(self.effect_settings.colour),
+            "effect_secondary_colour": effects::format_colour(self.effect_settings.secondary_colour),
+            "effect_speed": self.effect_settings.speed,
+            "transition_duration": self.transition_duration,
+            "udp_input_address": self.udp_input_address.clone(),
+            "udp_input_priority": self.udp_input_priority,
+            "audio_input": self.audio_settings.input.as_str(),
+            "audio_sample_rate": self.audio_settings.sample_rate,
+            "audio_channels": self.audio_settings.channels,
+            "frame_source": self.frame_source.input.as_str(),
+            "frame_source_rate": self.frame_source.frame_rate,
+            "frame_source_width": self.frame_source.width,
+            "frame_source_height": self.frame_source.height,
+            "frame_source_pixel_format": self.frame_source.pixel_format.name(),
+            "output_file": self.output_file.clone(),
+            "capture_downscale": self.capture_downscale,
+            "sampling_threads": self.sampling_threads
         };
-        println!("{}", json_out.to_string());
-        open_file.write(json_out.pretty(4).as_bytes()).unwrap();
+        fs::write(file_path, json_out.pretty(4))
+    }
+}
+
+pub fn is_configuration_path(path_in: &str) -> bool {
+    // another way of writing CONFIGURATION_PATH, like ./assets/program_configuration.json, is the same file
+    match (fs::canonicalize(path_in), fs::canonicalize(CONFIGURATION_PATH)) {
+        (Ok(path), Ok(configuration_path)) => path == configuration_path,
+        _ => path::Path::new(path_in) == path::Path::new(CONFIGURATION_PATH)
     }
-}
\ No newline at end of file
+}
+
+pub fn list_profiles() -> Vec<String> {
+    // names of the .json files in the profile directory, without the extension
+    let mut profiles: Vec<String> = match fs::read_dir(PROFILE_DIRECTORY) {
+        Ok(entries) => entries.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        }).collect(),
+        Err(_) => Vec::new()
+    };
+    profiles.sort();
+    profiles
+}
+
+pub fn is_same_serial_port(first: &str, second: &str) -> bool {
+    // Windows port names like COM3 don't care about case, device paths elsewhere do
+    if cfg!(target_os = "windows") {
+        first.eq_ignore_ascii_case(second)
+    } else {
+        first == second
+    }
+}
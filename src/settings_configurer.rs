@@ -1,17 +1,39 @@
 extern crate iced;
 
-use self::iced::{Container, Sandbox, Length, Row, Alignment, Text, TextInput, text_input, Column, Element, pick_list, PickList, Button, button};
+use self::iced::{Application, Command, Subscription, executor, keyboard, Container, Length, Row, Alignment, Text, TextInput, text_input, Column, Element, pick_list, PickList, Button, button, Slider, slider};
 use iced::settings::Settings;
 use self::iced::window::icon::Icon;
 use std::path::Path;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
 use image::{ImageError};
+use json::object;
 use std::process::exit;
 use iced::alignment::Horizontal;
-use crate::{baudrate, program_config};
+use iced::futures::channel::oneshot;
+use iced_native::{event, subscription, window, Event};
+use serialport::{SerialPortInfo, SerialPortType};
+use crate::{baudrate, calibration, control_server, program_config};
+use crate::calibration::IdentifyPattern;
+use crate::colour_correction::{self, ColourSettings};
+use crate::monitor_config::MonitorConfiguration;
+
+// the LEDs can't be refreshed faster than the serial port sends a frame, nor usefully slower than this
+const MIN_REFRESH_RATE: f32 = 1.0;
+const MAX_REFRESH_RATE: f32 = 240.0;
+const ERROR_COLOUR: [f32; 3] = [0.8, 0.0, 0.0];
+const SUCCESS_COLOUR: [f32; 3] = [0.0, 0.5, 0.0];
+// how long the running lights show the test pattern before going back to what they were showing
+const TEST_PATTERN_DURATION: Duration = Duration::from_secs(3);
 
 #[derive(Clone)]
 pub struct SettingsConfigurer {
-    port_options_state: pick_list::State<String>,
+    port_options_state: pick_list::State<PortOption>,
+    refresh_ports_state: button::State,
+    test_connection_state: button::State,
     baudrate_options_state: pick_list::State<baudrate::Baudrate>,
     refreshrate_state: text_input::State,
     save_path_state: text_input::State,
@@ -19,9 +41,35 @@ pub struct SettingsConfigurer {
     reset_button_state: button::State,
     ok_button_state: button::State,
     cancel_button_state: button::State,
-
+    undo_button_state: button::State,
+    redo_button_state: button::State,
+    connection_page_state: button::State,
+    colour_page_state: button::State,
+    brightness_state: slider::State,
+    gamma_state: slider::State,
+    saturation_state: slider::State,
+    white_balance_states: [slider::State; 3],
+    smoothing_state: slider::State,
+
+    page: Page,
+    // listed once and again when Refresh is pressed, looking for ports is slow on some platforms
+    port_options: Vec<PortOption>,
+    // what happened the last time Test connection was pressed, cleared when the port or baud rate changes
+    connection_test: Option<Result<String, String>>,
+    // a test is sent in the background, the button is disabled until it's done
+    testing_connection: bool,
+    // the colours the running worker was last told to show, so only changes are sent
+    pushed_colours: (f32, ColourSettings),
     current_values_index: usize,
     previous_states: Vec<FieldValues>,
+    // the field the newest history entry changed, more changes to it are merged into that entry
+    last_edited_field: Option<Field>,
+    // what's in the file, kept apart from the history so undoing past a save still shows the changes as unsaved
+    saved_values: FieldValues,
+    // why the last save didn't work, cleared by the next change
+    save_error: Option<String>,
+    // settings that aren't editable here are carried over from the loaded file when saving
+    loaded_configuration: program_config::ProgramConfiguration,
 }
 
 #[derive(Clone, PartialEq)]
@@ -30,19 +78,104 @@ struct FieldValues{
     selected_baudrate: baudrate::Baudrate,
     desired_refreshrate: String,
     save_file_path: String,
-    config_state: ConfigState,
+    brightness: f32,
+    colour_settings: ColourSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortOption {
+    name: String,
+    // manufacturer, product and serial number for USB ports, empty if there's nothing to add
+    description: String,
+    connected: bool,
+}
+
+impl PortOption {
+    fn from_info(info: &SerialPortInfo) -> PortOption {
+        let description = match &info.port_type {
+            SerialPortType::UsbPort(usb_info) => {
+                let mut details: Vec<String> = [&usb_info.manufacturer, &usb_info.product].into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect();
+                if details.is_empty() {
+                    details.push(format!("USB {:04x}:{:04x}", usb_info.vid, usb_info.pid));
+                }
+                if let Some(serial_number) = &usb_info.serial_number {
+                    details.push(format!("serial {}", serial_number));
+                }
+                details.join(" ")
+            }
+            SerialPortType::BluetoothPort => String::from("Bluetooth"),
+            SerialPortType::PciPort | SerialPortType::Unknown => String::new(),
+        };
+        PortOption {
+            name: info.port_name.clone(),
+            description,
+            connected: true,
+        }
+    }
+
+    fn missing(name: &str) -> PortOption {
+        PortOption {
+            name: name.to_string(),
+            description: String::new(),
+            connected: false,
+        }
+    }
+}
+
+impl Display for PortOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.connected {
+            write!(f, "{} (not connected)", self.name)
+        } else if self.description.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} - {}", self.name, self.description)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Connection,
+    Colour,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    PortSelected(String),
+    PortSelected(PortOption),
+    RefreshPorts,
+    TestConnection,
+    ConnectionTested(Result<String, String>),
     BaudrateSelected(baudrate::Baudrate),
     RefreshrateSelected(String),
     FilePathChanged(String),
+    PageSelected(Page),
+    BrightnessChanged(f32),
+    GammaChanged(f32),
+    SaturationChanged(f32),
+    WhiteBalanceChanged(usize, f32),
+    SmoothingChanged(f32),
+    Undo,
+    Redo,
     SaveFile,
     ResetSettings,
     SaveAndExit,
+    Cancel,
+}
 
+// fields that change many times in a row while being edited, a slider drag or a typed value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    RefreshRate,
+    SavePath,
+    Brightness,
+    Gamma,
+    Saturation,
+    WhiteBalance(usize),
+    Smoothing,
 }
 
 #[derive(Clone, PartialEq)]
@@ -53,9 +186,19 @@ enum ConfigState{
 
 impl Default for SettingsConfigurer {
     fn default() -> Self {
-        let default_info = program_config::ProgramConfiguration::load_from_file("assets/program_configuration.json");
+        let default_info = program_config::ProgramConfiguration::load_from_file(program_config::CONFIGURATION_PATH);
+        let loaded_values = FieldValues {
+            selected_port: default_info.serial_port.clone(),
+            selected_baudrate: default_info.baudrate,
+            desired_refreshrate: format!("{:.2}", default_info.refresh_rate),
+            save_file_path: String::from(program_config::CONFIGURATION_PATH),
+            brightness: default_info.brightness,
+            colour_settings: default_info.colour_settings,
+        };
         SettingsConfigurer {
             port_options_state: Default::default(),
+            refresh_ports_state: Default::default(),
+            test_connection_state: Default::default(),
             baudrate_options_state: Default::default(),
             refreshrate_state: Default::default(),
             save_path_state: Default::default(),
@@ -63,32 +206,73 @@ impl Default for SettingsConfigurer {
             reset_button_state: Default::default(),
             ok_button_state: Default::default(),
             cancel_button_state: Default::default(),
+            undo_button_state: Default::default(),
+            redo_button_state: Default::default(),
+            connection_page_state: Default::default(),
+            colour_page_state: Default::default(),
+            brightness_state: Default::default(),
+            gamma_state: Default::default(),
+            saturation_state: Default::default(),
+            white_balance_states: Default::default(),
+            smoothing_state: Default::default(),
+            page: Page::Connection,
+            port_options: SettingsConfigurer::get_serial_port_options(),
+            connection_test: None,
+            testing_connection: false,
+            pushed_colours: (default_info.brightness, default_info.colour_settings),
             current_values_index: 0,
-            previous_states: vec![
-                FieldValues {
-                    selected_port: default_info.serial_port,
-                    selected_baudrate: default_info.baudrate,
-                    desired_refreshrate: format!("{:.2}", default_info.refresh_rate),
-                    save_file_path: String::from("assets/program_configuration.json"),
-                    config_state: ConfigState::NoChanges
-                }
-            ]
+            previous_states: vec![loaded_values.clone()],
+            last_edited_field: None,
+            saved_values: loaded_values,
+            save_error: None,
+            loaded_configuration: default_info,
         }
     }
 }
 
-impl Sandbox for SettingsConfigurer {
+impl Application for SettingsConfigurer {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Flags = ();
+
     fn view(&mut self) -> Element<Message> {
         let render_values = self.get_values();
-
+        let connected_ports = self.connected_port_names();
+        let validation = render_values.validate(&connected_ports);
+
+        // the configured port stays in the list while it's unplugged so it's clear what's missing
+        let mut port_options = self.port_options.clone();
+        let selected_port = match port_options.iter().find(|option| option.name == render_values.selected_port) {
+            Some(option) => Some(option.clone()),
+            None if render_values.selected_port.is_empty() => None,
+            None => {
+                let missing = PortOption::missing(&render_values.selected_port);
+                port_options.insert(0, missing.clone());
+                Some(missing)
+            }
+        };
         let port_picker = PickList::new(
             &mut self.port_options_state,
-            SettingsConfigurer::get_serial_port_options(),
-            Some(render_values.selected_port),
+            port_options,
+            selected_port,
             Message::PortSelected,
         )
+            .placeholder("No serial port")
             .text_size(20);
 
+        let refresh_ports_button = Button::new(
+            &mut self.refresh_ports_state,
+            Text::new("Refresh")
+        ).on_press(Message::RefreshPorts);
+
+        let mut test_connection_button = Button::new(
+            &mut self.test_connection_state,
+            Text::new("Test connection")
+        );
+        if render_values.port_error(&connected_ports).is_none() && !self.testing_connection {
+            test_connection_button = test_connection_button.on_press(Message::TestConnection);
+        }
+
         let baudrate_picker = PickList::new(
             &mut self.baudrate_options_state,
             &baudrate::Baudrate::ALL[..],
@@ -107,37 +291,66 @@ impl Sandbox for SettingsConfigurer {
 
         let save_file_line = TextInput::new(
             &mut self.save_path_state,
-            "assets/program_configuration.json",
+            program_config::CONFIGURATION_PATH,
             render_values.save_file_path.as_str(),
             Message::FilePathChanged
         )
             .width(Length::Units(400));
 
-        let save_button = Button::new(
+        let mut save_button = Button::new(
             &mut self.save_button_state,
             Text::new("Save")
-        ).on_press(Message::SaveFile);
+        );
 
         let reset_button = Button::new(
             &mut self.reset_button_state,
             Text::new("Reset")
         ).on_press(Message::ResetSettings);
 
-        let ok_button = Button::new(
+        let mut ok_button = Button::new(
             &mut self.ok_button_state,
             Text::new("OK")
-        ).on_press(Message::SaveAndExit);
+        );
+        // nothing can be saved until every field makes sense
+        if validation.is_ok() {
+            save_button = save_button.on_press(Message::SaveFile);
+            ok_button = ok_button.on_press(Message::SaveAndExit);
+        }
 
+        let cancel_button = Button::new(
+            &mut self.cancel_button_state,
+            Text::new("Cancel")
+        ).on_press(Message::Cancel);
+
+        let mut undo_button = Button::new(
+            &mut self.undo_button_state,
+            Text::new("Undo")
+        );
+        if self.current_values_index > 0 {
+            undo_button = undo_button.on_press(Message::Undo);
+        }
 
+        let mut redo_button = Button::new(
+            &mut self.redo_button_state,
+            Text::new("Redo")
+        );
+        if self.current_values_index + 1 < self.previous_states.len() {
+            redo_button = redo_button.on_press(Message::Redo);
+        }
 
-        let port_baud_row = Row::new()
+        let port_row = Row::new()
             .align_items(Alignment::Start)
             .spacing(10)
             .push(Text::new("Communicate on"))
             .push(port_picker)
+            .push(refresh_ports_button);
+        let baud_row = Row::new()
+            .align_items(Alignment::Start)
+            .spacing(10)
             .push(Text::new("at"))
             .push(baudrate_picker)
-            .push(Text::new("baud."));
+            .push(Text::new("baud."))
+            .push(test_connection_button);
         let framerate_row = Row::new()
             .align_items(Alignment::Start)
             .spacing(10)
@@ -155,15 +368,75 @@ impl Sandbox for SettingsConfigurer {
             .push(save_button)
             .push(reset_button)
             .push(ok_button)
+            .push(cancel_button)
             .spacing(15)
             .align_items(Alignment::End);
 
-        let selection_column = Column::new()
-            .push(port_baud_row)
-            .push(framerate_row)
-            .push(save_file_row)
-            .push(save_reset_cancel_row)
+        let mut connection_page_button = Button::new(
+            &mut self.connection_page_state,
+            Text::new("Connection")
+        );
+        let mut colour_page_button = Button::new(
+            &mut self.colour_page_state,
+            Text::new("Colour")
+        );
+        // the button for the page that's showing does nothing
+        match self.page {
+            Page::Connection => colour_page_button = colour_page_button.on_press(Message::PageSelected(Page::Colour)),
+            Page::Colour => connection_page_button = connection_page_button.on_press(Message::PageSelected(Page::Connection)),
+        }
+        let page_row = Row::new()
+            .push(connection_page_button)
+            .push(colour_page_button)
+            .push(undo_button)
+            .push(redo_button)
+            .spacing(15);
+
+        let mut selection_column = Column::new()
+            .push(page_row)
             .spacing(20);
+        selection_column = match self.page {
+            Page::Connection => {
+                selection_column = selection_column.push(port_row);
+                if let Some(error) = render_values.port_error(&connected_ports) {
+                    selection_column = selection_column.push(SettingsConfigurer::error_text(error));
+                }
+                selection_column = selection_column.push(baud_row);
+                match self.connection_test.clone() {
+                    Some(Ok(result)) => selection_column = selection_column.push(Text::new(result).size(18).color(SUCCESS_COLOUR)),
+                    Some(Err(error)) => selection_column = selection_column.push(SettingsConfigurer::error_text(error)),
+                    None => {}
+                }
+                selection_column = selection_column.push(framerate_row);
+                if let Some(error) = render_values.refresh_rate_error() {
+                    selection_column = selection_column.push(SettingsConfigurer::error_text(error));
+                }
+                selection_column = selection_column.push(save_file_row);
+                if let Some(error) = render_values.save_path_error() {
+                    selection_column = selection_column.push(SettingsConfigurer::error_text(error));
+                }
+                selection_column
+            }
+            Page::Colour => {
+                let colour_settings = render_values.colour_settings;
+                let [red_state, green_state, blue_state] = &mut self.white_balance_states;
+                selection_column
+                    .push(SettingsConfigurer::slider_row("Brightness", Slider::new(&mut self.brightness_state, 0.0..=1.0, render_values.brightness, Message::BrightnessChanged), render_values.brightness))
+                    .push(SettingsConfigurer::slider_row("Gamma", Slider::new(&mut self.gamma_state, 0.5..=3.0, colour_settings.gamma, Message::GammaChanged), colour_settings.gamma))
+                    .push(SettingsConfigurer::slider_row("Saturation", Slider::new(&mut self.saturation_state, 0.0..=2.0, colour_settings.saturation, Message::SaturationChanged), colour_settings.saturation))
+                    .push(SettingsConfigurer::slider_row("Red", Slider::new(red_state, 0.0..=1.0, colour_settings.white_balance[0], |value| Message::WhiteBalanceChanged(0, value)), colour_settings.white_balance[0]))
+                    .push(SettingsConfigurer::slider_row("Green", Slider::new(green_state, 0.0..=1.0, colour_settings.white_balance[1], |value| Message::WhiteBalanceChanged(1, value)), colour_settings.white_balance[1]))
+                    .push(SettingsConfigurer::slider_row("Blue", Slider::new(blue_state, 0.0..=1.0, colour_settings.white_balance[2], |value| Message::WhiteBalanceChanged(2, value)), colour_settings.white_balance[2]))
+                    .push(SettingsConfigurer::slider_row("Smoothing", Slider::new(&mut self.smoothing_state, 0.0..=colour_correction::MAX_SMOOTHING, colour_settings.smoothing, Message::SmoothingChanged), colour_settings.smoothing))
+            }
+        };
+        if let Some(error) = self.save_error.clone() {
+            selection_column = selection_column.push(SettingsConfigurer::error_text(error));
+        } else if let (Page::Colour, Err(error)) = (self.page, validation) {
+            // the fields with the problem aren't on this page
+            selection_column = selection_column.push(SettingsConfigurer::error_text(error));
+        }
+        selection_column = selection_column.push(save_reset_cancel_row);
 
         Container::new(selection_column)
             .center_x()
@@ -174,14 +447,12 @@ impl Sandbox for SettingsConfigurer {
             .into()
     }
 
-    type Message = Message;
-
-    fn new() -> Self {
-        Self::default()
+    fn new(_flags: ()) -> (Self, Command<Message>) {
+        (Self::default(), Command::none())
     }
 
     fn title(&self) -> String {
-        match self.get_values().config_state{
+        match self.config_state(){
             ConfigState::UnsavedChanges => {
                 String::from("Configure Ambient Lighting Settings | Unsaved Changes")
             },
@@ -191,51 +462,133 @@ impl Sandbox for SettingsConfigurer {
         }
     }
 
-    fn update(&mut self, message: Self::Message) {
+    fn update(&mut self, message: Message) -> Command<Message> {
         let mut new_state = self.get_values();
+        // anything else done in between, like undoing or saving, starts a new history entry
+        let last_edited_field = self.last_edited_field.take();
+        let mut edited_field = None;
         match message {
             Message::PortSelected(port) => {
-                new_state.selected_port = port;
+                new_state.selected_port = port.name;
+                self.connection_test = None;
             }
             Message::BaudrateSelected(baudrate) => {
                 new_state.selected_baudrate = baudrate;
+                self.connection_test = None;
+            }
+            Message::RefreshPorts => {
+                self.port_options = SettingsConfigurer::get_serial_port_options();
+                return Command::none()
+            }
+            Message::TestConnection => {
+                self.connection_test = None;
+                self.testing_connection = true;
+                return self.test_connection()
+            }
+            Message::ConnectionTested(result) => {
+                self.connection_test = Some(result);
+                self.testing_connection = false;
+                return Command::none()
             }
             Message::RefreshrateSelected(refreshrate) => {
                 new_state.desired_refreshrate = refreshrate;
+                edited_field = Some(Field::RefreshRate);
             }
             Message::FilePathChanged(path) => {
                 new_state.save_file_path = path;
+                edited_field = Some(Field::SavePath);
+            }
+            Message::PageSelected(page) => {
+                // switching page isn't a change that can be undone
+                self.page = page;
+                return Command::none()
+            }
+            Message::BrightnessChanged(brightness) => {
+                new_state.brightness = brightness;
+                edited_field = Some(Field::Brightness);
+            }
+            Message::GammaChanged(gamma) => {
+                new_state.colour_settings.gamma = gamma;
+                edited_field = Some(Field::Gamma);
+            }
+            Message::SaturationChanged(saturation) => {
+                new_state.colour_settings.saturation = saturation;
+                edited_field = Some(Field::Saturation);
+            }
+            Message::WhiteBalanceChanged(channel, balance) => {
+                new_state.colour_settings.white_balance[channel] = balance;
+                edited_field = Some(Field::WhiteBalance(channel));
+            }
+            Message::SmoothingChanged(smoothing) => {
+                new_state.colour_settings.smoothing = smoothing;
+                edited_field = Some(Field::Smoothing);
+            }
+
+            Message::Undo => {
+                if self.current_values_index > 0 {
+                    self.current_values_index -= 1;
+                    self.push_colours();
+                }
+                return Command::none()
+            }
+            Message::Redo => {
+                if self.current_values_index + 1 < self.previous_states.len() {
+                    self.current_values_index += 1;
+                    self.push_colours();
+                }
+                return Command::none()
             }
 
             Message::SaveFile => {
+                // the history is kept so the save can still be undone
                 self.save_config();
-                // the current state is the new default
-                self.previous_states = self.previous_states.split_off(self.current_values_index);
-                self.current_values_index = 0;
+                return Command::none()
             }
 
             Message::ResetSettings => {
-                // the 0th index is the last setting configuration that can be reset to
-                self.current_values_index = 0;
-                // remove the extra values
-                self.previous_states.truncate(1);
-                // the 0th index should by default be saved already
-                return
+                // going back to what's saved is a change of its own so it can be undone too
+                new_state = self.saved_values.clone();
             }
             Message::SaveAndExit => {
-                self.save_config();
+                // stay open if it didn't save so the error can be seen
+                if self.save_config() {
+                    exit(0);
+                }
+                return Command::none()
+            }
+            Message::Cancel => {
+                // the worker has to go back to the saved colours or the discarded ones stay on the LEDs
+                self.previous_states.push(self.saved_values.clone());
+                self.current_values_index = self.previous_states.len() - 1;
+                self.push_colours();
                 exit(0);
             }
         }
-        new_state.config_state = ConfigState::NoChanges;
-        if new_state != self.get_values()
-        {
-            new_state.config_state = ConfigState::UnsavedChanges;
+        if new_state == self.get_values() {
+            // nothing changed so there's nothing to undo, a slider sending the same value again doesn't end the drag
+            if edited_field == last_edited_field {
+                self.last_edited_field = last_edited_field;
+            }
+            return Command::none()
+        }
+        self.save_error = None;
+
+        let at_newest_entry = self.current_values_index + 1 == self.previous_states.len();
+        if edited_field.is_some() && edited_field == last_edited_field && at_newest_entry && self.current_values_index > 0 {
+            // a whole slider drag or typed value is undone in one step
+            self.previous_states[self.current_values_index] = new_state;
+        } else {
+            self.current_values_index+=1;
+            self.previous_states.truncate(self.current_values_index);
+            self.previous_states.push(new_state);
         }
+        self.last_edited_field = edited_field;
+        self.push_colours();
+        Command::none()
+    }
 
-        self.current_values_index+=1;
-        self.previous_states.truncate(self.current_values_index);
-        self.previous_states.push(new_state);
+    fn subscription(&self) -> Subscription<Message> {
+        subscription::events_with(SettingsConfigurer::handle_event)
     }
 }
 
@@ -244,21 +597,79 @@ impl SettingsConfigurer {
         self.previous_states[self.current_values_index].clone()
     }
 
+    fn config_state(&self) -> ConfigState {
+        if self.previous_states[self.current_values_index] == self.saved_values {
+            ConfigState::NoChanges
+        } else {
+            ConfigState::UnsavedChanges
+        }
+    }
+
+    fn handle_event(event: Event, _status: event::Status) -> Option<Message> {
+        // Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes, closing the window is the same as Cancel
+        match event {
+            Event::Window(window::Event::CloseRequested) => Some(Message::Cancel),
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }) if modifiers.control() => match key_code {
+                keyboard::KeyCode::Z if modifiers.shift() => Some(Message::Redo),
+                keyboard::KeyCode::Z => Some(Message::Undo),
+                keyboard::KeyCode::Y => Some(Message::Redo),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
     fn save_config(&mut self) -> bool{
         /*
         Does not handle undo/redo stack, only manages file saving
         */
         let current_values = self.get_values();
-        if !current_values.save_file_path.is_empty() {
-            let current_config_state = self.get_current_configuration();
-            current_config_state.save_to_file(current_values.save_file_path.as_str());
-            true
+        let result = self.get_current_configuration()
+            .and_then(|configuration| configuration.save_to_file(current_values.save_file_path.as_str())
+                .map_err(|error| format!("Unable to save {}: {}", current_values.save_file_path, error)));
+        match result {
+            Ok(()) => {
+                // a copy saved somewhere else isn't what the lights load, so there's nothing for them to reload
+                if program_config::is_configuration_path(current_values.save_file_path.as_str()) {
+                    control_server::post_to_tray("/api/reload", object!{});
+                }
+                self.saved_values = current_values;
+                self.save_error = None;
+                true
+            }
+            Err(error) => {
+                self.save_error = Some(error);
+                false
+            }
         }
-        else {
-            false
+    }
+
+    fn error_text(error: String) -> Text {
+        Text::new(error).size(18).color(ERROR_COLOUR)
+    }
+    fn push_colours(&mut self) {
+        /*
+        Sends the colour settings to the control server of the tray that launched the configurer,
+        so the strip changes while a slider is dragged. They're only saved to the file with the rest
+        */
+        let current_values = self.get_values();
+        let colours = (current_values.brightness, current_values.colour_settings);
+        if colours != self.pushed_colours {
+            colour_correction::send_colours_to_tray(colours.0, &colours.1);
+            self.pushed_colours = colours;
         }
     }
-    fn get_serial_port_options() -> Vec<String> {
+
+    fn slider_row<'a>(label: &str, slider: Slider<'a, f32, Message>, value: f32) -> Row<'a, Message> {
+        Row::new()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(Text::new(label).width(Length::Units(130)))
+            .push(slider.step(0.01).width(Length::Units(300)))
+            .push(Text::new(format!("{:.2}", value)))
+    }
+
+    fn get_serial_port_options() -> Vec<PortOption> {
         let mut ports_out = Vec::new();
         let available_ports = match serialport::available_ports() {
             Ok(portlist) => portlist,
@@ -266,18 +677,55 @@ impl SettingsConfigurer {
         };
 
         for port in available_ports {
-            ports_out.push(port.port_name)
+            ports_out.push(PortOption::from_info(&port))
         }
         ports_out
     }
 
-    pub fn get_current_configuration(&self) -> program_config::ProgramConfiguration {
+    fn connected_port_names(&self) -> Vec<String> {
+        self.port_options.iter().map(|option| option.name.clone()).collect()
+    }
+
+    fn test_connection(&self) -> Command<Message> {
+        /*
+        Lights each side in its own colour with the selected port and baud rate.
+        Opening the port and waiting on it happens in the background so the window doesn't freeze
+        */
         let current_values = self.get_values();
-        program_config::ProgramConfiguration {
+        let test_configuration = program_config::ProgramConfiguration {
+            serial_port: current_values.selected_port.clone(),
+            baudrate: current_values.selected_baudrate,
+            ..self.loaded_configuration.clone()
+        };
+        // the running lights hold the saved port open, and on Windows nothing else can open it meanwhile
+        let running_baudrate = if program_config::is_same_serial_port(&test_configuration.serial_port, &self.loaded_configuration.serial_port) {
+            Some(self.loaded_configuration.baudrate)
+        } else {
+            None
+        };
+        let colours = calibration::identify_colours(&MonitorConfiguration::load_from_file("assets/monitor_configuration.json"), IdentifyPattern::Sides);
+        // the test sleeps and waits on the port, so it gets a thread of its own rather than holding up the executor
+        let (result_sender, result_receiver) = oneshot::channel();
+        thread::spawn(move || {
+            let _ = result_sender.send(send_test_pattern(test_configuration, colours, running_baudrate));
+        });
+        Command::perform(
+            async move { result_receiver.await.unwrap_or_else(|_| Err(String::from("The connection test stopped unexpectedly"))) },
+            Message::ConnectionTested
+        )
+    }
+
+    pub fn get_current_configuration(&self) -> Result<program_config::ProgramConfiguration, String> {
+        let current_values = self.get_values();
+        current_values.validate(&self.connected_port_names())?;
+        Ok(program_config::ProgramConfiguration {
             serial_port: current_values.selected_port,
-            refresh_rate: current_values.desired_refreshrate.parse().unwrap(),
-            baudrate: current_values.selected_baudrate
-        }
+            refresh_rate: current_values.desired_refreshrate.trim().parse().map_err(|_| "Invalid refresh rate".to_string())?,
+            baudrate: current_values.selected_baudrate,
+            brightness: current_values.brightness,
+            colour_settings: current_values.colour_settings,
+            ..self.loaded_configuration.clone()
+        })
     }
 
     pub fn default_window_settings(path_in_opt: Option<&str>) -> Settings<()> {
@@ -285,8 +733,10 @@ impl SettingsConfigurer {
         if path_in_opt.is_some(){
             SettingsConfigurer::try_set_icon(&mut out, path_in_opt.unwrap()).unwrap();
         }
-        out.window.size = (570, 230);
+        out.window.size = (760, 560);
         out.window.resizable = false;
+        // Cancel puts the saved colours back on the lights before exiting
+        out.exit_on_close_request = false;
         out.default_text_size = 25;
         out
     }
@@ -302,4 +752,79 @@ impl SettingsConfigurer {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl FieldValues {
+    fn validate(&self, port_options: &[String]) -> Result<(), String> {
+        match self.port_error(port_options).or_else(|| self.refresh_rate_error()).or_else(|| self.save_path_error()) {
+            Some(error) => Err(error),
+            None => Ok(())
+        }
+    }
+
+    fn port_error(&self, port_options: &[String]) -> Option<String> {
+        if self.selected_port.is_empty() {
+            Some(String::from("Choose the serial port the LEDs are plugged into"))
+        } else if !port_options.contains(&self.selected_port) {
+            Some(format!("Serial port {} isn't connected", self.selected_port))
+        } else {
+            None
+        }
+    }
+
+    fn refresh_rate_error(&self) -> Option<String> {
+        match self.desired_refreshrate.trim().parse::<f32>() {
+            Ok(rate) if (MIN_REFRESH_RATE..=MAX_REFRESH_RATE).contains(&rate) => None,
+            _ => Some(format!("The refresh rate has to be a number from {} to {}", MIN_REFRESH_RATE, MAX_REFRESH_RATE))
+        }
+    }
+
+    fn save_path_error(&self) -> Option<String> {
+        // only catches the obvious problems, anything else shows up when saving
+        if self.save_file_path.trim().is_empty() {
+            return Some(String::from("Choose a file to save to"));
+        }
+        let path = Path::new(&self.save_file_path);
+        if path.is_dir() {
+            return Some(format!("{} is a folder", self.save_file_path));
+        }
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !parent.is_dir() => {
+                return Some(format!("The folder {} doesn't exist", parent.display()));
+            }
+            _ => {}
+        }
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.permissions().readonly() => Some(format!("{} is read only", self.save_file_path)),
+            _ => None
+        }
+    }
+}
+
+fn send_test_pattern(test_configuration: program_config::ProgramConfiguration, colours: Vec<u8>, running_baudrate: Option<baudrate::Baudrate>) -> Result<String, String> {
+    /*
+    The saved port is tested through the running lights when the window was opened from the tray and the baud rate
+    hasn't changed, they show the pattern for a few seconds then go back to what they were showing.
+    running_baudrate is the baud rate the lights have the port open at, None when it's another port
+    */
+    if colours.is_empty() {
+        return Err(String::from("No LEDs are configured to send a test pattern to"));
+    }
+    if running_baudrate == Some(test_configuration.baudrate) && calibration::send_identify_to_tray(Some(&colours)) {
+        thread::sleep(TEST_PATTERN_DURATION);
+        calibration::send_identify_to_tray(None);
+        return Ok(format!("Sent a test pattern to {} at {} baud through the running lights, each side should have lit in a different colour", test_configuration.serial_port, test_configuration.baudrate));
+    }
+    let mut port = serialport::open_with_settings(test_configuration.serial_port.as_str(), &test_configuration.get_serial_port_settings())
+        .map_err(|error| match running_baudrate {
+            Some(running_baudrate) if running_baudrate != test_configuration.baudrate => format!(
+                "Unable to open {}: {}. The running lights have it open at {} baud, save the new baud rate to try it",
+                test_configuration.serial_port, error, running_baudrate
+            ),
+            _ => format!("Unable to open {}: {}", test_configuration.serial_port, error)
+        })?;
+    port.write_all(&colours)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Unable to write to {}: {}", test_configuration.serial_port, error))?;
+    Ok(format!("Sent a test pattern to {} at {} baud, each side should be a different colour", test_configuration.serial_port, test_configuration.baudrate))
+}
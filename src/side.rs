@@ -1,6 +1,6 @@
 use std::fmt::{Display, Formatter, Result};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Side{
     TOP,
     LEFT,
@@ -21,6 +21,31 @@ impl From<char> for Side{
     }
 }
 
+impl Side{
+    pub fn opposite(&self) -> Side {
+        match self{
+            Side::TOP => Side::BOTTOM,
+            Side::BOTTOM => Side::TOP,
+            Side::LEFT => Side::RIGHT,
+            Side::RIGHT => Side::LEFT,
+            Side::ERROR => Side::ERROR
+        }
+    }
+}
+
+impl From<Side> for char{
+    fn from(side: Side) -> Self {
+        match side{
+            Side::TOP => 'T',
+            Side::BOTTOM => 'B',
+            Side::LEFT => 'L',
+            Side::RIGHT => 'R',
+            Side::ERROR => 'E'
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SideDirection{
     pub side: Side,
     pub direction: Side
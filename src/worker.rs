@@ -2,13 +2,21 @@
 extern crate serialport;
 extern crate scrap;
 
-use std::{thread, time, io};
+use std::{fs, thread, time, io, mem};
 use std::io::Write;
-use std::ops::Deref;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::sync::mpsc::{Sender, SyncSender, TrySendError};
+use crate::audio::{AudioInputSettings, AudioStyle, AudioVisualiser};
+use crate::effects::{self, EffectEngine, EffectKind, LedPlacement};
+use crate::capture::MonitorCapture;
+use crate::colour_correction::{ColourCorrection, ColourSettings};
+use crate::frame_source::FrameSourceSettings;
 use crate::framerate::FramerateLimiter;
 use crate::kernel::Kernel;
 use crate::monitor_config::MonitorConfiguration;
-use crate::program_config::ProgramConfiguration;
+use crate::multiplexer::{InputContent, InputSource, Multiplexer, MODE_PRIORITY};
+use crate::program_config::{self, ProgramConfiguration};
 
 #[derive(Debug)]
 pub enum Error{
@@ -17,102 +25,623 @@ pub enum Error{
 }
 
 pub enum ControlMessage {
-    StopWorker,
-    UpdateConfiguration
+    // the sender is notified once the LEDs have faded out and the outputs are flushed
+    StopWorker(Sender<()>),
+    UpdateConfiguration,
+    // settings that didn't come from the saved file, like a profile picked from the tray, they aren't saved
+    ApplyConfiguration(Box<ProgramConfiguration>),
+    // reopens the capture with the LED layout saved by the monitor setup wizard
+    UpdateMonitorConfiguration,
+    Pause,
+    Resume,
+    Off,
+    SetBlankOnExit(bool),
+    SetOutputMode(OutputMode),
+    SetBrightness(f32),
+    SetColourSettings(ColourSettings),
+    // show the content until the timeout runs out or a higher priority input takes over
+    SetInput{source: InputSource, priority: u8, content: InputContent, timeout: Option<time::Duration>},
+    ClearInput(InputSource),
+    // every frame written to the serial port is also offered to subscribers, frames are dropped for slow subscribers
+    SubscribeOutput(SyncSender<Arc<[u8]>>),
+    RequestStatus(Sender<WorkerStatus>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkerState {
+    // capturing the screen and writing to the LEDs
+    Running,
+    // not capturing, the LEDs keep showing the last frame
+    Paused,
+    // not capturing, the LEDs are set to black
+    Off,
+}
+
+impl Default for WorkerState {
+    fn default() -> Self {
+        WorkerState::Running
+    }
+}
+
+impl Display for WorkerState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                WorkerState::Running => "running",
+                WorkerState::Paused => "paused",
+                WorkerState::Off => "off"
+            }
+        )
+    }
+}
+
+impl WorkerState {
+    pub fn from_name(name: &str) -> Option<WorkerState> {
+        match name {
+            "running" => Some(WorkerState::Running),
+            "paused" => Some(WorkerState::Paused),
+            "off" => Some(WorkerState::Off),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    Capture,
+    Effect(EffectKind),
+    Audio(AudioStyle),
+}
+
+impl Default for OutputMode {
+    fn default() -> Self {
+        OutputMode::Capture
+    }
+}
+
+impl Display for OutputMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputMode::Capture => write!(f, "Screen Capture"),
+            OutputMode::Effect(kind) => write!(f, "{}", kind),
+            OutputMode::Audio(style) => write!(f, "{}", style)
+        }
+    }
+}
+
+impl OutputMode {
+    pub(crate) const ALL: [OutputMode; 9] = [
+        OutputMode::Capture,
+        OutputMode::Effect(EffectKind::StaticColour),
+        OutputMode::Effect(EffectKind::StaticGradient),
+        OutputMode::Effect(EffectKind::RainbowSwirl),
+        OutputMode::Effect(EffectKind::Breathing),
+        OutputMode::Effect(EffectKind::ColourCycle),
+        OutputMode::Audio(AudioStyle::VuMeter),
+        OutputMode::Audio(AudioStyle::Spectrum),
+        OutputMode::Audio(AudioStyle::BeatPulse)
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputMode::Capture => "capture",
+            OutputMode::Effect(kind) => kind.name(),
+            OutputMode::Audio(style) => style.name()
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OutputMode> {
+        OutputMode::ALL.iter().find(|mode| mode.name() == name).copied()
+    }
+}
+
+#[derive(Clone)]
+pub struct WorkerStatus {
+    pub state: WorkerState,
+    pub output_mode: OutputMode,
+    pub active_input: Option<(InputSource, u8)>,
+    pub blank_on_exit: bool,
+    pub brightness: f32,
+    pub screen_width: usize,
+    pub screen_height: usize,
+    pub pixel_locations: Vec<[usize; 2]>,
+    // how long capturing and sampling the last new frame took
+    pub sampling_time: time::Duration,
+    // frames written over the last second or so
+    pub frame_rate: f32,
+    // where the colours are going, the serial port name, file or none
+    pub output: String,
+    // why the lights aren't doing what was asked, like a serial port that went away or an audio input that couldn't be opened
+    pub error: Option<String>,
+}
+
+// seconds, longer transitions than this aren't useful
+const MAX_TRANSITION_DURATION: f32 = 60.0;
+// how often a serial port that stopped taking writes, like an unplugged strip, is opened again
+const RECONNECT_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+// output_file value that throws the colours away, for previewing without LED hardware
+pub const DISCARD_OUTPUT: &str = "none";
+
+pub enum LedOutput {
+    // None while the port can't be opened, like when the strip is unplugged, until reconnect_output opens it
+    Serial(Option<Box<dyn serialport::SerialPort>>),
+    // raw colours appended frame after frame, for running without LED hardware
+    File(fs::File),
+    Discard,
+}
+
+impl LedOutput {
+    pub fn name(&self) -> String {
+        match self {
+            LedOutput::Serial(port) => port.as_ref().and_then(|port| port.name()).unwrap_or_else(|| String::from("serial port")),
+            LedOutput::File(_) => String::from("file"),
+            LedOutput::Discard => String::from(DISCARD_OUTPUT)
+        }
+    }
+}
+
+impl Write for LedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LedOutput::Serial(Some(port)) => port.write(buf),
+            LedOutput::Serial(None) => Err(io::Error::new(io::ErrorKind::NotConnected, "the serial port isn't open")),
+            LedOutput::File(file) => file.write(buf),
+            LedOutput::Discard => Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LedOutput::Serial(Some(port)) => port.flush(),
+            LedOutput::Serial(None) => Ok(()),
+            LedOutput::File(file) => file.flush(),
+            LedOutput::Discard => Ok(())
+        }
+    }
+}
+
+fn transition_duration(seconds: f32) -> time::Duration {
+    // a huge or broken value from the configuration file would otherwise panic
+    time::Duration::from_secs_f32(seconds.clamp(0.0, MAX_TRANSITION_DURATION))
 }
 
 pub struct Worker {
-    pub open_serial_port: Box<dyn serialport::SerialPort>,
-    pub display_capturer: scrap::Capturer,
+    pub led_output: LedOutput,
+    // what the serial port is opened again with after writing to it fails
+    serial_port_name: String,
+    serial_port_settings: serialport::SerialPortSettings,
+    // why the last frame couldn't be written, None once writing works again
+    output_error: Option<String>,
+    last_reconnect_attempt: time::Instant,
+    monitor_capture: MonitorCapture,
+    frame_source_settings: FrameSourceSettings,
     blur_kernel: Kernel,
     refreshrate: FramerateLimiter,
-    pixel_locations: Vec<[usize; 2]>,
-    captured_image: Vec<u8>
+    // reused every frame, holds the last colours sampled from the screen
+    captured_colours: Vec<u8>,
+    sampling_time: time::Duration,
+    // frames written since frame_rate_start, turned into frame_rate once a second has passed
+    frame_count: u32,
+    frame_rate_start: time::Instant,
+    frame_rate: f32,
+    brightness: f32,
+    colour_correction: ColourCorrection,
+    output_subscribers: Vec<SyncSender<Arc<[u8]>>>,
+    state: WorkerState,
+    blank_on_exit: bool,
+    fade_duration: f32,
+    fade_on_pause: bool,
+    // both reused every frame, last_output is copied into rather than replaced
+    output_colours: Vec<u8>,
+    last_output: Vec<u8>,
+    output_mode: OutputMode,
+    effect_engine: EffectEngine,
+    led_placements: Vec<LedPlacement>,
+    multiplexer: Multiplexer,
+    audio_settings: AudioInputSettings,
+    // only running while an audio mode is selected
+    audio_visualiser: Option<AudioVisualiser>,
+    audio_error: Option<String>,
 }
 
 
 impl Worker {
-    pub fn new(p_config: ProgramConfiguration, monitor_config: MonitorConfiguration, b_kernel: Kernel, display_index: usize) -> Result<Worker, Error> {
-        let display_capturer = Worker::get_display_capturer(&monitor_config, display_index);
-        let pixel_locations = monitor_config.get_pixel_locations(&b_kernel).unwrap();
-
-        let open_serial_port = match p_config.get_open_serial_port(){
-            Some(port) => port,
-            None => {
-                eprintln!("Failed to open serial port!");
-                return Err(Error::OpenSerialError)
-            }
+    pub fn new(p_config: ProgramConfiguration, monitor_config: MonitorConfiguration, b_kernel: Kernel) -> Result<Worker, Error> {
+        let monitor_capture = MonitorCapture::open(&p_config.frame_source, &monitor_config, &b_kernel, p_config.capture_downscale, p_config.sampling_threads);
+
+        let led_output = match &p_config.output_file {
+            Some(path) if path == DISCARD_OUTPUT => LedOutput::Discard,
+            Some(path) => match fs::File::create(path) {
+                Ok(file) => LedOutput::File(file),
+                Err(error) => {
+                    eprintln!("Failed to create output file {}: {}", path, error);
+                    return Err(Error::OpenSerialError)
+                }
+            },
+            // a port that isn't plugged in yet is opened by reconnect_output once it appears
+            None => LedOutput::Serial(p_config.get_open_serial_port())
+        };
+        let output_error = match led_output {
+            LedOutput::Serial(None) => {
+                let message = format!("Unable to open serial port {}", p_config.serial_port);
+                eprintln!("{}", message);
+                Some(message)
+            },
+            _ => None
         };
 
-        let unwrapped_display_capturer = match display_capturer{
-            Ok(capturer) => capturer,
+        let unwrapped_monitor_capture = match monitor_capture{
+            Ok(capture) => capture,
             Err(_err) => {eprintln!("{}", _err); return Err(Error::OpenCapturerError)}
         };
+        let led_placements = Worker::get_led_placements(&unwrapped_monitor_capture, &monitor_config);
 
-        Ok(Worker{
-            open_serial_port: open_serial_port,
-            display_capturer: unwrapped_display_capturer,
+        let mut worker = Worker{
+            led_output,
+            serial_port_name: p_config.serial_port.clone(),
+            serial_port_settings: p_config.get_serial_port_settings(),
+            output_error,
+            last_reconnect_attempt: time::Instant::now(),
+            monitor_capture: unwrapped_monitor_capture,
+            frame_source_settings: p_config.frame_source.clone(),
             blur_kernel: b_kernel,
             refreshrate: p_config.get_refreshrate_controller(),
-            pixel_locations: pixel_locations,
-            captured_image: Vec::new()
-        })
+            captured_colours: Vec::new(),
+            sampling_time: time::Duration::ZERO,
+            frame_count: 0,
+            frame_rate_start: time::Instant::now(),
+            frame_rate: 0.0,
+            brightness: p_config.brightness.clamp(0.0, 1.0),
+            colour_correction: ColourCorrection::new(p_config.colour_settings, p_config.brightness.clamp(0.0, 1.0)),
+            output_subscribers: Vec::new(),
+            state: WorkerState::Running,
+            blank_on_exit: p_config.blank_on_exit,
+            fade_duration: p_config.fade_duration.max(0.0),
+            fade_on_pause: p_config.fade_on_pause,
+            output_colours: Vec::new(),
+            last_output: Vec::new(),
+            output_mode: OutputMode::Capture,
+            effect_engine: EffectEngine::new(p_config.effect_settings),
+            led_placements,
+            multiplexer: Multiplexer::new(transition_duration(p_config.transition_duration)),
+            audio_settings: p_config.audio_settings.clone(),
+            audio_visualiser: None,
+            audio_error: None,
+        };
+        worker.set_output_mode(p_config.output_mode);
+        // restore the state the program was left in
+        worker.set_state(p_config.worker_state);
+        Ok(worker)
     }
     pub fn tick(&mut self){
         self.refreshrate.tick();
     }
 
-    fn get_display_capturer(monitor_config: &MonitorConfiguration, display_index: usize) -> io::Result<scrap::Capturer>{
-        let display_config_info = monitor_config.monitors.get(display_index).unwrap();
-        scrap::Capturer::new(scrap::Display::all().unwrap().remove(display_config_info.monitor_number-1))
+    fn get_led_placements(monitor_capture: &MonitorCapture, monitor_config: &MonitorConfiguration) -> Vec<LedPlacement> {
+        // effects treat the monitors as one wide screen
+        let (width, height, pixel_locations) = monitor_capture.layout();
+        effects::place_leds(&pixel_locations, &monitor_config.get_led_sides(), width, height)
     }
 
     pub fn read_and_output(&mut self) {
         // locks on the display capturer and serial port should be acquireable with very little
         // blocking since the only time they're acquired elsewhere is for the purpose of modifying
         // the serial output mode and display capturer from the taskbar
+        let calibrating = self.multiplexer.active_input().is_some_and(|(source, _priority)| source == InputSource::Calibration);
+        match self.state {
+            WorkerState::Running => self.update_mode_input(),
+            // identifying LEDs still works while paused or off so the lights don't have to be turned back on for it
+            _ if calibrating => {},
+            // nothing to capture, the LEDs already show the paused frame or black
+            _ => return
+        }
+        // taken out of self while it's written so write_output can borrow the rest of the worker
+        let mut output_colours = mem::take(&mut self.output_colours);
+        if self.multiplexer.output(&self.effect_engine, &self.led_placements, &mut output_colours) {
+            // identify colours are lit exactly as given, a dim or smoothed LED is easy to miss
+            if !calibrating {
+                self.colour_correction.apply(&mut output_colours);
+            }
+            self.write_output(&output_colours);
+        }
+        self.output_colours = output_colours;
+    }
 
-        match self.display_capturer.frame() {
-            Ok(frame) => self.captured_image = frame.to_vec(),
+    fn update_mode_input(&mut self) {
+        match self.output_mode {
+            OutputMode::Capture => {
+                // if no new frame is ready the multiplexer keeps using the last captured colours
+                if self.capture_colours() {
+                    self.multiplexer.set_colours(InputSource::Capture, MODE_PRIORITY, &self.captured_colours);
+                }
+            },
+            // effects don't need the screen so no capturing is done while one is active
+            OutputMode::Effect(kind) => self.multiplexer.set_input(InputSource::Effect, MODE_PRIORITY, InputContent::Effect(kind), None),
+            OutputMode::Audio(style) => {
+                if let Some(audio_visualiser) = self.audio_visualiser.as_mut() {
+                    let colours = audio_visualiser.render(style, self.effect_engine.settings.colour, &self.led_placements);
+                    self.multiplexer.set_input(InputSource::Audio, MODE_PRIORITY, InputContent::Colours(colours), None);
+                }
+            }
+        };
+    }
+
+    fn capture_colours(&mut self) -> bool {
+        let sample_start = time::Instant::now();
+        let new_frame = self.monitor_capture.sample(&mut self.captured_colours);
+        if new_frame {
+            self.sampling_time = sample_start.elapsed();
+        } else {
+            // Wait until function is called again to try and capture another screenshot
+            thread::sleep(time::Duration::new(1, 0) / self.refreshrate.tick_rate as u32);
+        }
+        new_frame
+    }
+
+    fn write_output(&mut self, output_colours: &[u8]) {
+        if self.output_error.is_some() {
+            self.reconnect_output();
+        }
+        match self.led_output.write_all(output_colours) {
+            Ok(()) => {
+                self.output_error = None;
+                self.count_frame();
+            },
             Err(error) => {
-                if error.kind() == std::io::ErrorKind::WouldBlock {
-                    // Wait until function is called again to try and capture another screenshot
-                    thread::sleep(time::Duration::new(1, 0) / self.refreshrate.tick_rate as u32);
+                // an unplugged strip is reported in the status and retried rather than stopping the worker
+                let message = format!("Unable to write to {}: {}", self.output_name(), error);
+                if self.output_error.is_none() {
+                    eprintln!("{}", message);
                 }
+                self.output_error = Some(message);
+                // closed so the port is free to be opened again once it's back
+                if let LedOutput::Serial(port) = &mut self.led_output {
+                    *port = None;
+                }
+            }
+        }
+        self.publish_output(output_colours);
+        self.last_output.clear();
+        self.last_output.extend_from_slice(output_colours);
+    }
+
+    fn reconnect_output(&mut self) {
+        if let LedOutput::Serial(port) = &mut self.led_output {
+            if self.last_reconnect_attempt.elapsed() < RECONNECT_INTERVAL {
+                return;
+            }
+            self.last_reconnect_attempt = time::Instant::now();
+            if let Ok(reopened) = serialport::open_with_settings(self.serial_port_name.as_str(), &self.serial_port_settings) {
+                *port = Some(reopened);
+            }
+        }
+    }
+
+    fn output_name(&self) -> String {
+        match &self.led_output {
+            // a closed port can't say what it's called
+            LedOutput::Serial(None) => self.serial_port_name.clone(),
+            led_output => led_output.name()
+        }
+    }
+
+    fn count_frame(&mut self) {
+        self.frame_count += 1;
+        let elapsed = self.frame_rate_start.elapsed();
+        if elapsed >= time::Duration::from_secs(1) {
+            self.frame_rate = self.frame_count as f32 / elapsed.as_secs_f32();
+            self.frame_count = 0;
+            self.frame_rate_start = time::Instant::now();
+        }
+    }
+
+    fn write_blank(&mut self) {
+        self.write_output(&vec![0; self.monitor_capture.led_count() * 3]);
+    }
+
+    pub fn fade_out(&mut self) {
+        /*
+        Blocks for fade_duration while stepping the last frame down to black at the refresh rate.
+        No control messages are handled meanwhile, so whoever waits on the worker, like stop_worker
+        and through it the tray's Quit, waits for the fade as well
+        */
+        let starting_colours = self.last_output.clone();
+        if !starting_colours.is_empty() {
+            let steps = (self.fade_duration * self.refreshrate.tick_rate).round() as usize;
+            let mut faded_colours = Vec::with_capacity(starting_colours.len());
+            for step in 1..steps {
+                let remaining = 1.0 - step as f32 / steps as f32;
+                faded_colours.clear();
+                faded_colours.extend(starting_colours.iter().map(|colour| (*colour as f32 * remaining) as u8));
+                self.write_output(&faded_colours);
+                self.tick();
             }
+        }
+        self.write_blank();
+    }
+
+    pub fn set_state(&mut self, state: WorkerState) {
+        let fading = match state {
+            WorkerState::Off => self.state != WorkerState::Off,
+            // an off strip is already black
+            WorkerState::Paused => self.fade_on_pause && self.state == WorkerState::Running,
+            WorkerState::Running => false,
         };
-        if self.captured_image.len() == 0{
+        if fading {
+            self.fade_out();
+        }
+        self.state = state;
+    }
+
+    pub fn set_output_mode(&mut self, output_mode: OutputMode) {
+        let previous_mode = self.output_mode;
+        self.output_mode = output_mode;
+        // the new mode fills its own slot on the next frame
+        self.multiplexer.clear_input(InputSource::Capture);
+        self.multiplexer.clear_input(InputSource::Effect);
+        self.multiplexer.clear_input(InputSource::Audio);
+
+        // the audio input is only held open while it's being visualised
+        match output_mode {
+            OutputMode::Audio(_) => {
+                if self.audio_visualiser.is_none() {
+                    match AudioVisualiser::start(&self.audio_settings) {
+                        Ok(audio_visualiser) => {
+                            self.audio_visualiser = Some(audio_visualiser);
+                            self.audio_error = None;
+                        },
+                        Err(error) => {
+                            let message = format!("Unable to open audio input {}: {}", self.audio_settings.input, error);
+                            eprintln!("{}", message);
+                            self.audio_error = Some(message);
+                            // with no audio the LEDs would freeze, so whatever was showing before carries on
+                            self.set_output_mode(match previous_mode {
+                                OutputMode::Audio(_) => OutputMode::Capture,
+                                mode => mode
+                            });
+                        }
+                    }
+                }
+            },
+            _ => self.audio_visualiser = None
+        }
+    }
+
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode
+    }
+
+    pub fn set_input(&mut self, source: InputSource, priority: u8, content: InputContent, timeout: Option<time::Duration>) {
+        self.multiplexer.set_input(source, priority, content, timeout);
+    }
+
+    pub fn clear_input(&mut self, source: InputSource) {
+        self.multiplexer.clear_input(source);
+        if source == InputSource::Calibration && self.state != WorkerState::Running {
+            // the frame shown before identifying started is gone, so a paused strip goes dark like one that's off
+            self.write_blank();
+        }
+    }
+
+    pub fn set_blank_on_exit(&mut self, blank_on_exit: bool) {
+        self.blank_on_exit = blank_on_exit;
+    }
+
+    pub fn shutdown(&mut self) {
+        if self.blank_on_exit && self.state != WorkerState::Off {
+            self.fade_out();
+        }
+        if let Err(error) = self.led_output.flush() {
+            eprintln!("Unable to flush serial port: {}", error);
+        }
+        // dropping the subscribers closes their streams
+        self.output_subscribers.clear();
+    }
+
+    fn publish_output(&mut self, output_colours: &[u8]) {
+        if self.output_subscribers.is_empty() {
             return;
         }
-        let mut output_colours = Vec::new();
-        for point in self.pixel_locations.deref() {
-            output_colours.extend_from_slice(&self.blur_kernel.kernel_pass_result(&self.captured_image, self.display_capturer.width(), self.display_capturer.height(), point[0], point[1]));
-        };
-        self.open_serial_port.write_all(output_colours.as_slice()).expect("Could not write to serial port");
+        // one copy of the frame is shared by every subscriber
+        let frame: Arc<[u8]> = Arc::from(output_colours);
+        self.output_subscribers.retain(|subscriber| {
+            match subscriber.try_send(Arc::clone(&frame)) {
+                Ok(_) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false
+            }
+        });
+    }
+
+    pub fn add_output_subscriber(&mut self, subscriber: SyncSender<Arc<[u8]>>) {
+        self.output_subscribers.push(subscriber);
+    }
+
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        self.colour_correction.set_brightness(self.brightness);
+    }
+
+    pub fn set_colour_settings(&mut self, colour_settings: ColourSettings) {
+        self.colour_correction.set_settings(colour_settings);
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        let (screen_width, screen_height, pixel_locations) = self.monitor_capture.layout();
+        WorkerStatus {
+            state: self.state,
+            output_mode: self.output_mode,
+            active_input: self.multiplexer.active_input(),
+            blank_on_exit: self.blank_on_exit,
+            brightness: self.brightness,
+            screen_width,
+            screen_height,
+            pixel_locations,
+            sampling_time: self.sampling_time,
+            // a strip that can't be written to matters more than the audio input
+            error: self.output_error.clone().or_else(|| self.audio_error.clone()),
+            // nothing is written while paused or off so an old frame rate would be misleading
+            frame_rate: if self.frame_rate_start.elapsed() > time::Duration::from_secs(2) { 0.0 } else { self.frame_rate },
+            output: self.output_name(),
+        }
     }
 
     pub fn update_settings(&mut self, p_config: Option<ProgramConfiguration>, monitor_config: Option<MonitorConfiguration>, conv_kernel: Option<Kernel>){
         if p_config.is_some() {
             let program_config_info = p_config.unwrap();
-            match self.open_serial_port.name() {
-                Some(name) => {
-                    if name.to_uppercase() != program_config_info.serial_port {
-                        // new serial port
-                        match program_config_info.get_open_serial_port() {
-                            Some(port) => self.open_serial_port = port,
-                            None => { println!("Unable to open serial port") }
-                        }
-                    } else {
-                        self.open_serial_port.set_all(&program_config_info.get_serial_port_settings()).unwrap();
+            self.set_brightness(program_config_info.brightness);
+            self.set_colour_settings(program_config_info.colour_settings);
+            self.blank_on_exit = program_config_info.blank_on_exit;
+            self.fade_duration = program_config_info.fade_duration.max(0.0);
+            self.fade_on_pause = program_config_info.fade_on_pause;
+            if program_config_info.audio_settings != self.audio_settings {
+                // reopened with the new settings by set_output_mode
+                self.audio_settings = program_config_info.audio_settings.clone();
+                self.audio_visualiser = None;
+            }
+            self.set_output_mode(program_config_info.output_mode);
+            if program_config_info.capture_downscale.max(1) != self.monitor_capture.downscale() {
+                self.monitor_capture.set_downscale(program_config_info.capture_downscale);
+            }
+            self.monitor_capture.set_sampling_threads(program_config_info.sampling_threads);
+            self.effect_engine.settings = program_config_info.effect_settings;
+            self.multiplexer.set_transition_duration(transition_duration(program_config_info.transition_duration));
+            self.serial_port_name = program_config_info.serial_port.clone();
+            self.serial_port_settings = program_config_info.get_serial_port_settings();
+            // an output file stays in place until the program is restarted
+            // ports that can't be opened or set up are left closed for reconnect_output to open
+            if let LedOutput::Serial(open_serial_port) = &mut self.led_output {
+                let same_port = match open_serial_port {
+                    Some(port) => port.name().is_some_and(|name| program_config::is_same_serial_port(&name, &self.serial_port_name)),
+                    None => false
+                };
+                if same_port {
+                    if let Some(Err(error)) = open_serial_port.as_mut().map(|port| port.set_all(&self.serial_port_settings)) {
+                        self.output_error = Some(format!("Unable to set up serial port {}: {}", self.serial_port_name, error));
+                        *open_serial_port = None;
+                    }
+                } else {
+                    // the old port is closed first so reopening the same one isn't refused as busy
+                    *open_serial_port = None;
+                    *open_serial_port = serialport::open_with_settings(self.serial_port_name.as_str(), &self.serial_port_settings).ok();
+                    if open_serial_port.is_none() {
+                        self.output_error = Some(format!("Unable to open serial port {}", self.serial_port_name));
                     }
                 }
-                None => { eprintln!("Failed to retrieve current port name") }
             }
         }
-        if monitor_config.is_some() {
-            match Worker::get_display_capturer(&monitor_config.unwrap(), 0) {
-                Ok(cap) => {
-                    self.display_capturer = cap;
+        if let Some(kernel) = conv_kernel {
+            self.blur_kernel = kernel;
+            self.monitor_capture.set_kernel(&kernel);
+        }
+
+        if let Some(monitor_config) = monitor_config {
+            match MonitorCapture::open(&self.frame_source_settings, &monitor_config, &self.blur_kernel, self.monitor_capture.downscale(), self.monitor_capture.sampling_threads()) {
+                Ok(capture) => {
+                    self.led_placements = Worker::get_led_placements(&capture, &monitor_config);
+                    self.monitor_capture = capture;
                 },
                 Err(e) => match e {
                     _ => {
@@ -121,7 +650,5 @@ impl Worker {
                 },
             }
         }
-
-        self.blur_kernel =  if conv_kernel.is_some() {conv_kernel.unwrap()} else{self.blur_kernel};
     }
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+//Runs the whole capture to output pipeline with --headless --output-file and checks the colours written
+use std::{env, fs, process, thread};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const LEFT_LEDS: usize = 10;
+const TOP_LEDS: usize = 17;
+const RIGHT_LEDS: usize = 10;
+const BOTTOM_LEDS: usize = 16;
+const LED_COUNT: usize = LEFT_LEDS + TOP_LEDS + RIGHT_LEDS + BOTTOM_LEDS;
+// the strip starts at the bottom left corner and runs clockwise, left, top, right then bottom
+const MONITOR_CONFIGURATION: &str = r#"{
+    "monitor_configuration": [
+        {
+            "monitor": 1,
+            "led_order": "LTTRRBBL",
+            "diagonal_size": 24.1,
+            "led_count": {"right": 10, "top": 17, "left": 10, "bottom": 16},
+            "leds_per_inch": 1.3,
+            "bezel_thickness": {"top": 1.063, "bottom": 1.063, "left": 1.063, "right": 1.063}
+        }
+    ]
+}"#;
+// no smoothing or blending so every frame is exactly what was sampled
+const PROGRAM_CONFIGURATION: &str = r#"{
+    "refresh_rate": 30,
+    "baud_rate": 115200,
+    "serial_port": "none",
+    "transition_duration": 0
+}"#;
+const FRAMES_TO_WAIT_FOR: usize = 5;
+const RUN_TIME_LIMIT: Duration = Duration::from_secs(30);
+// sampling and colour correction round in floating point
+const COLOUR_TOLERANCE: i16 = 2;
+
+fn working_directory(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("ambient_lighting_{}_{}", name, process::id()));
+    fs::create_dir_all(directory.join("assets")).unwrap();
+    fs::write(directory.join("assets/monitor_configuration.json"), MONITOR_CONFIGURATION).unwrap();
+    fs::write(directory.join("assets/program_configuration.json"), PROGRAM_CONFIGURATION).unwrap();
+    directory
+}
+
+fn run_headless(directory: &Path, frame_source: &str) -> Vec<u8> {
+    /*
+    Runs the program on frame_source until it has written a few frames then stops it,
+    returns the last whole frame written
+    */
+    let output_path = directory.join("leds.bin");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monitor_ambient_lighting_rust_edition"))
+        .current_dir(directory)
+        .args(["--headless", "--mode", "capture", "--frame-source", frame_source, "--frame-size", "640x360", "--output-file"])
+        .arg(&output_path)
+        .spawn()
+        .expect("Unable to start the program");
+    let started = Instant::now();
+    let mut written = Vec::new();
+    while written.len() < FRAMES_TO_WAIT_FOR * LED_COUNT * 3 && started.elapsed() < RUN_TIME_LIMIT {
+        thread::sleep(Duration::from_millis(100));
+        written = fs::read(&output_path).unwrap_or_default();
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(directory).ok();
+
+    let whole_frames = written.len() / (LED_COUNT * 3);
+    assert!(whole_frames >= FRAMES_TO_WAIT_FOR, "only {} bytes were written", written.len());
+    written[(whole_frames - 1) * LED_COUNT * 3..whole_frames * LED_COUNT * 3].to_vec()
+}
+
+fn assert_colour(frame: &[u8], led: usize, expected: [u8; 3]) {
+    let colour = &frame[led * 3..led * 3 + 3];
+    let close = colour.iter().zip(expected).all(|(channel, expected)| (*channel as i16 - expected as i16).abs() <= COLOUR_TOLERANCE);
+    assert!(close, "LED {} is {:?}, expected {:?}", led, colour, expected);
+}
+
+fn write_ppm(path: &Path, width: usize, height: usize, colour: [u8; 3]) {
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for _ in 0..width * height {
+        ppm.extend_from_slice(&colour);
+    }
+    fs::write(path, ppm).unwrap();
+}
+
+#[test]
+fn image_colour_reaches_every_led() {
+    let directory = working_directory("image");
+    let image_path = directory.join("frame.ppm");
+    let colour = [10, 200, 30];
+    write_ppm(&image_path, 640, 360, colour);
+    let frame = run_headless(&directory, image_path.to_str().unwrap());
+    for led in 0..LED_COUNT {
+        assert_colour(&frame, led, colour);
+    }
+}
+
+#[test]
+fn side_bars_light_each_side_in_its_colour() {
+    let frame = run_headless(&working_directory("side_bars"), "pattern:side_bars");
+    // the corners take the top and bottom bar colours, so only the middle LED of each side is checked
+    let sides = [
+        (0, LEFT_LEDS, [255, 255, 0]),
+        (LEFT_LEDS, TOP_LEDS, [255, 0, 0]),
+        (LEFT_LEDS + TOP_LEDS, RIGHT_LEDS, [0, 255, 0]),
+        (LEFT_LEDS + TOP_LEDS + RIGHT_LEDS, BOTTOM_LEDS, [0, 0, 255])
+    ];
+    for (first_led, led_count, colour) in sides {
+        assert_colour(&frame, first_led + led_count / 2, colour);
+    }
+}